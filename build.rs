@@ -7,17 +7,26 @@ use std::path::PathBuf;
 ///
 /// This function performs the following operations:
 /// 1. Retrieves the build directory path from the `OUT_DIR` environment variable.
-/// 2. Copies the `memory.x` file into the build directory.
+/// 2. Copies the memory layout into the build directory as `memory.x`: the
+///    RP2040 one (`memory-rp2040.x`) for `thumbv6m-none-eabi`, otherwise the
+///    STM32F103 one (`memory-stm32f103.x`). Neither source is named
+///    `memory.x`, because the linker would find it in the package root first.
 /// 3. Instructs the Rust compiler to search for `memory.x` in the build directory during linking.
-/// 4. Sets up a trigger to rerun the build script if `memory.x` changes.
+/// 4. Sets up a trigger to rerun the build script if either layout changes.
 fn main() {
 	let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
 
+	let memory: &[u8] = if env::var("TARGET").unwrap() == "thumbv6m-none-eabi" {
+		include_bytes!("memory-rp2040.x")
+	} else {
+		include_bytes!("memory-stm32f103.x")
+	};
 	File::create(out.join("memory.x"))
 		.unwrap()
-		.write_all(include_bytes!("memory.x"))
+		.write_all(memory)
 		.unwrap();
 
 	println!("cargo:rustc-link-search={}", out.display());
-	println!("cargo:rerun-if-changed=memory.x");
+	println!("cargo:rerun-if-changed=memory-stm32f103.x");
+	println!("cargo:rerun-if-changed=memory-rp2040.x");
 }
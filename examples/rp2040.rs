@@ -0,0 +1,102 @@
+#![cfg_attr(target_arch = "arm", no_std)]
+#![cfg_attr(target_arch = "arm", no_main)]
+
+/// The firmware, only built for the Raspberry Pi Pico (`thumbv6m-none-eabi`)
+#[cfg(target_arch = "arm")]
+mod firmware {
+    use panic_probe as _;
+    use rp2040_hal::{
+        self as hal,
+        clocks::{init_clocks_and_plls, Clock},
+        pac,
+        pwm::Slices,
+        Sio,
+        Watchdog,
+    };
+    use embedded_hal::PwmPin;
+    use led_effects::LEDEffect;
+
+    #[cfg(feature = "defmt")]
+    use defmt_rtt as _;
+
+    /// Second-stage bootloader for the W25Q080 flash of the Raspberry Pi Pico.
+    #[link_section = ".boot2"]
+    #[used]
+    pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_W25Q080;
+
+    /// Frequency of the Pico's crystal oscillator.
+    const XTAL_FREQ_HZ: u32 = 12_000_000;
+
+    /// Entry point of the embedded application.
+    ///
+    /// This function brings the RP2040 up at its default 125 MHz system clock
+    /// and drives the Pico's on-board LED (GPIO25, PWM slice 4 channel B). The
+    /// slice's channel implements `embedded_hal::PwmPin` with a `u16` duty, so
+    /// it is handed to `LEDEffect` as is. The measured system clock is passed to
+    /// `set_clock_hz`, which the busy-wait delays need to be timed correctly;
+    /// alternatively, build with the `clk-125mhz` feature. The breathing and
+    /// heartbeat effects then alternate in an infinite loop.
+    #[hal::entry]
+    fn main() -> ! {
+        let mut dp = pac::Peripherals::take().unwrap();
+        let mut watchdog = Watchdog::new(dp.WATCHDOG);
+        let sio = Sio::new(dp.SIO);
+
+        let clocks = init_clocks_and_plls(
+            XTAL_FREQ_HZ,
+            dp.XOSC,
+            dp.CLOCKS,
+            dp.PLL_SYS,
+            dp.PLL_USB,
+            &mut dp.RESETS,
+            &mut watchdog,
+        )
+        .unwrap();
+
+        let pins = hal::gpio::Pins::new(
+            dp.IO_BANK0,
+            dp.PADS_BANK0,
+            sio.gpio_bank0,
+            &mut dp.RESETS,
+        );
+
+        let pwm_slices = Slices::new(dp.PWM, &mut dp.RESETS);
+        let mut pwm = pwm_slices.pwm4;
+        pwm.set_ph_correct();
+        pwm.enable();
+
+        let mut channel = pwm.channel_b;
+        channel.output_to(pins.gpio25);
+        let max_duty = channel.get_max_duty();
+
+        #[cfg(feature = "defmt")]
+        defmt::info!("PWM channel enabled with max duty: {}", max_duty);
+
+        let mut led = LEDEffect::new(channel, max_duty / 50, max_duty)
+            .expect("Failed to create LED effect");
+        led.set_clock_hz(clocks.system_clock.freq().to_Hz())
+            .expect("Failed to set the clock frequency");
+
+        #[cfg(feature = "defmt")]
+        defmt::info!("LED Effects Demo Starting...");
+
+        loop {
+            if let Err(_) = led.breath_secs(5) {
+                #[cfg(feature = "defmt")]
+                defmt::error!("Breathing effect failed");
+                continue;
+            }
+
+            if let Err(_) = led.heartbeat(4, 2, 60) {
+                #[cfg(feature = "defmt")]
+                defmt::error!("Heartbeat effect failed");
+                continue;
+            }
+        }
+    }
+}
+
+/// Nothing to run on the host; this lets `cargo test` build the example
+/// without the firmware for the Pico.
+#[cfg(not(target_arch = "arm"))]
+fn main() {}
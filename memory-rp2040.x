@@ -0,0 +1,18 @@
+MEMORY
+{
+  /* The RP2040 boots from 2M of external flash; the first 256 bytes hold BOOT2 */
+  BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
+  FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+  RAM : ORIGIN = 0x20000000, LENGTH = 256K
+}
+
+EXTERN(BOOT2_FIRMWARE)
+
+SECTIONS
+{
+  /* The second-stage bootloader must come first in flash */
+  .boot2 ORIGIN(BOOT2) :
+  {
+    KEEP(*(.boot2));
+  } > BOOT2
+} INSERT BEFORE .text;
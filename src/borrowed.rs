@@ -0,0 +1,99 @@
+//! Running effects on a pin that is only borrowed
+//!
+//! `LEDEffect` normally owns its pin. When the same PWM channel is shared
+//! with other code (e.g. status blinks driven directly), wrap a `&mut` to it
+//! in a [`PinRef`] for the duration of an effect, or use one of the free
+//! functions below, which do exactly that and hand the pin back afterwards.
+//! Effects run this way use the default settings (`EndState::Off`, the
+//! [`DEFAULT_CLOCK_HZ`](crate::DEFAULT_CLOCK_HZ) clock, no phase or master
+//! dimming).
+
+use embedded_hal::PwmPin;
+
+use crate::{DutyValue, Error, LEDEffect};
+
+/// A `PwmPin` that forwards to a mutably borrowed pin
+///
+/// Lets a short-lived `LEDEffect` drive a pin that stays owned elsewhere:
+///
+/// ```ignore
+/// {
+///     let mut led = LEDEffect::new(PinRef::new(&mut pwm_ch), 5, max)?;
+///     led.breath(3000)?;
+/// }
+/// pwm_ch.set_duty(max); // the pin is available again
+/// ```
+pub struct PinRef<'a, P> {
+    pin: &'a mut P,
+}
+
+impl<'a, P> PinRef<'a, P> {
+    /// Borrow `pin` for use with an `LEDEffect`
+    pub fn new(pin: &'a mut P) -> Self {
+        Self { pin }
+    }
+}
+
+impl<P: PwmPin> PwmPin for PinRef<'_, P> {
+    type Duty = P::Duty;
+
+    fn disable(&mut self) {
+        self.pin.disable()
+    }
+
+    fn enable(&mut self) {
+        self.pin.enable()
+    }
+
+    fn get_duty(&self) -> Self::Duty {
+        self.pin.get_duty()
+    }
+
+    fn get_max_duty(&self) -> Self::Duty {
+        self.pin.get_max_duty()
+    }
+
+    fn set_duty(&mut self, duty: Self::Duty) {
+        self.pin.set_duty(duty)
+    }
+}
+
+/// Run `LEDEffect::breath` on a borrowed pin
+pub fn breath_on<P>(pin: &mut P, pwm_min: P::Duty, pwm_max: P::Duty, duration_ms: u32) -> Result<(), Error>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    LEDEffect::new(PinRef::new(pin), pwm_min, pwm_max)?.breath(duration_ms)
+}
+
+/// Run `LEDEffect::heartbeat` on a borrowed pin
+pub fn heartbeat_on<P>(
+    pin: &mut P,
+    pwm_min: P::Duty,
+    pwm_max: P::Duty,
+    flash_beats: u32,
+    grouped_as: u32,
+    bpm: u32,
+) -> Result<(), Error>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    LEDEffect::new(PinRef::new(pin), pwm_min, pwm_max)?.heartbeat(flash_beats, grouped_as, bpm)
+}
+
+/// Run `LEDEffect::fade` on a borrowed pin, starting from its current duty
+pub fn fade_on<P>(
+    pin: &mut P,
+    pwm_min: P::Duty,
+    pwm_max: P::Duty,
+    target: P::Duty,
+    duration_ms: u32,
+) -> Result<(), Error>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    LEDEffect::new(PinRef::new(pin), pwm_min, pwm_max)?.fade(target, duration_ms)
+}
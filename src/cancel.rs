@@ -0,0 +1,81 @@
+//! Aborting effects from an interrupt
+//!
+//! A [`Cancel`] is a flag that can be raised from anywhere, including an
+//! interrupt handler, to stop the effect currently running through one of
+//! the `_cancellable` methods of `LEDEffect`. The effect only borrows it
+//! for the duration of the call, so it can live anywhere; to raise it from
+//! an interrupt, declare it as a `static`:
+//!
+//! ```ignore
+//! static STOP: Cancel = Cancel::new();
+//!
+//! #[interrupt]
+//! fn EXTI0() {
+//!     STOP.cancel();
+//! }
+//!
+//! led.breath_cancellable(4000, &STOP)?;
+//! STOP.reset();
+//! ```
+
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A cancellation flag shared between effects and interrupt handlers
+///
+/// Only uses atomic loads and stores, so it works on cores without
+/// compare-and-swap such as the Cortex-M0.
+#[derive(Debug, Default)]
+pub struct Cancel(AtomicBool);
+
+impl Cancel {
+    /// A flag that is not raised
+    pub const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    /// Raise the flag, stopping the running cancellable effect after its
+    /// current step
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Whether the flag is raised
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Lower the flag again
+    ///
+    /// The flag stays raised until reset, so every cancellable effect
+    /// started in the meantime returns right away.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// The `Cancel` a running effect watches, with the lifetime of its borrow erased
+#[derive(Clone, Copy)]
+pub(crate) struct Watched(NonNull<Cancel>);
+
+// SAFETY: a `Watched` stands in for a `&Cancel`, which is `Send` and `Sync`
+// because `Cancel` only holds an atomic.
+unsafe impl Send for Watched {}
+unsafe impl Sync for Watched {}
+
+impl Watched {
+    /// Watch `cancel`
+    ///
+    /// # Safety
+    ///
+    /// The `Watched` must not be used after the borrow of `cancel` ends.
+    pub(crate) unsafe fn new(cancel: &Cancel) -> Self {
+        Self(NonNull::from(cancel))
+    }
+
+    /// Whether the watched flag is raised
+    pub(crate) fn is_cancelled(&self) -> bool {
+        // SAFETY: `new` requires the borrow to still be alive.
+        unsafe { self.0.as_ref() }.is_cancelled()
+    }
+}
@@ -0,0 +1,103 @@
+//! Plain-data configuration for storing an `LEDEffect` setup in flash
+//!
+//! A [`Config`] is a `repr(C)` struct made only of integers, with no
+//! padding, so it can be copied to and from flash byte for byte without a
+//! serialization library. Use `LEDEffect::to_config` to capture a setup
+//! and `LEDEffect::from_config` to rebuild it on boot.
+
+#[cfg(feature = "defmt")]
+use defmt::Format;
+
+use crate::kind::EffectKind;
+use crate::Error;
+
+/// `Config::effect` value meaning "no default effect"
+pub const EFFECT_NONE: u32 = 0;
+/// `Config::effect` value for `EffectKind::Breath` (`params[0]` = duration)
+pub const EFFECT_BREATH: u32 = 1;
+/// `Config::effect` value for `EffectKind::Heartbeat`
+/// (`params` = flash beats, grouping, bpm)
+pub const EFFECT_HEARTBEAT: u32 = 2;
+/// `Config::effect` value for `EffectKind::Staircase` (`params` = levels, hold)
+pub const EFFECT_STAIRCASE: u32 = 3;
+/// `Config::effect` value for `EffectKind::Fade` (`params` = from, to, duration)
+pub const EFFECT_FADE: u32 = 4;
+/// `Config::effect` value for `EffectKind::Blink` (`params` = on, off, times)
+pub const EFFECT_BLINK: u32 = 5;
+/// `Config::effect` value for `EffectKind::Throb`
+/// (`params` = attack, decay, cycles)
+pub const EFFECT_THROB: u32 = 6;
+/// `Config::effect` value for `EffectKind::Cycle` (`params` = period, cycles)
+pub const EFFECT_CYCLE: u32 = 7;
+/// `Config::effect` value for `EffectKind::Countdown` (`params[0]` = total)
+pub const EFFECT_COUNTDOWN: u32 = 8;
+/// `Config::effect` value for `EffectKind::HeartbeatRealistic`
+/// (`params` = bpm, beats)
+pub const EFFECT_HEARTBEAT_REALISTIC: u32 = 9;
+/// `Config::effect` value for `EffectKind::LayeredBreath`
+/// (`params` = small cycles, small low, big cycles)
+pub const EFFECT_LAYERED_BREATH: u32 = 10;
+/// `Config::effect` value for `EffectKind::Sparkle`
+/// (`params` = duration, density, seed)
+pub const EFFECT_SPARKLE: u32 = 11;
+/// `Config::effect` value for `EffectKind::OrganicBreath`
+/// (`params` = duration, seed)
+pub const EFFECT_ORGANIC_BREATH: u32 = 12;
+/// `Config::effect` value for `EffectKind::FaultCode` (`params` = code, repeats)
+pub const EFFECT_FAULT_CODE: u32 = 13;
+/// `Config::effect` value for `EffectKind::Distress` (`params[0]` = repeats)
+pub const EFFECT_DISTRESS: u32 = 14;
+
+/// Persistable `LEDEffect` configuration
+///
+/// The layout is fixed at 32 bytes with no padding. Flags are stored as
+/// `u8`s rather than `bool`s so that any byte pattern read back from flash
+/// is a valid value; a non-zero flag counts as set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+#[repr(C)]
+pub struct Config {
+    /// Lowest animated duty (`pwm_min`)
+    pub pwm_min: u32,
+    /// Highest animated duty (`pwm_max`)
+    pub pwm_max: u32,
+    /// Core clock frequency in Hz (see `LEDEffect::set_clock_hz`)
+    pub clock_hz: u32,
+    /// Default effect, one of the `EFFECT_*` constants
+    pub effect: u32,
+    /// Parameters of the default effect, in declaration order
+    pub params: [u32; 3],
+    /// Whether `set_level` applies gamma correction
+    pub gamma: u8,
+    /// Whether the output is inverted (see `LEDEffect::set_inverted`)
+    pub inverted: u8,
+    /// Reserved for future use; write as zero
+    pub reserved: [u8; 2],
+}
+
+const _: () = assert!(core::mem::size_of::<Config>() == 32);
+
+impl Config {
+    /// Decode the default effect
+    ///
+    /// Returns `Ok(None)` for `EFFECT_NONE` and `Error::InvalidParameter`
+    /// for an unknown effect number.
+    pub fn default_effect(&self) -> Result<Option<EffectKind>, Error> {
+        match self.effect {
+            EFFECT_NONE => Ok(None),
+            code => EffectKind::from_code(code, self.params)
+                .map(Some)
+                .ok_or(Error::InvalidParameter),
+        }
+    }
+
+    /// Encode `effect` as the default effect
+    pub fn set_default_effect(&mut self, effect: Option<EffectKind>) {
+        let (tag, params) = match effect {
+            None => (EFFECT_NONE, [0; 3]),
+            Some(effect) => (effect.code(), effect.params()),
+        };
+        self.effect = tag;
+        self.params = params;
+    }
+}
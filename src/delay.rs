@@ -0,0 +1,81 @@
+//! Where the waits between effect steps come from
+//!
+//! By default an `LEDEffect` busy-waits with [`BusyWait`], counting core
+//! cycles at the clock set with `LEDEffect::set_clock_hz`. Any embedded-hal
+//! `DelayMs<u32>` can be used instead, e.g. a timer-based delay that keeps
+//! working when the core clock changes, or a mock that records the time
+//! requested in host tests:
+//!
+//! ```ignore
+//! let delay = cortex_m::delay::Delay::new(cp.SYST, clocks.sysclk().to_Hz());
+//! let mut led = LEDEffect::with_delay(pwm_ch, 5, max_duty, delay)?;
+//! ```
+//!
+//! A `set_yield` hook or a software PWM pin (see `LEDEffect::new_soft`)
+//! still takes over the waits from either.
+
+use embedded_hal::blocking::delay::DelayMs;
+
+#[cfg(target_arch = "arm")]
+use cortex_m::asm;
+
+/// A source of millisecond waits for `LEDEffect`
+pub trait Delay {
+    /// Wait for `ms` milliseconds
+    ///
+    /// `cycles_per_ms` is the core clock configured on the effect, for
+    /// delays that count cycles; others can ignore it. `LEDEffect` splits
+    /// long waits so that `ms * cycles_per_ms` always fits in a `u32`.
+    fn wait_ms(&mut self, ms: u32, cycles_per_ms: u32);
+}
+
+/// Busy-wait by counting core cycles, the default `Delay`
+///
+/// Only waits on Cortex-M targets; elsewhere (e.g. in host builds) it
+/// returns at once. A wait longer than `u32::MAX` cycles is cut short, which
+/// `LEDEffect` never asks for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BusyWait;
+
+impl Delay for BusyWait {
+    fn wait_ms(&mut self, ms: u32, cycles_per_ms: u32) {
+        let cycles = ms.saturating_mul(cycles_per_ms);
+        #[cfg(target_arch = "arm")]
+        asm::delay(cycles);
+        #[cfg(not(target_arch = "arm"))]
+        let _ = cycles;
+    }
+}
+
+impl<T> Delay for T
+where
+    T: DelayMs<u32>,
+{
+    fn wait_ms(&mut self, ms: u32, _cycles_per_ms: u32) {
+        self.delay_ms(ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delay that records the milliseconds it was asked to wait.
+    struct Recorder(u32);
+
+    impl DelayMs<u32> for Recorder {
+        fn delay_ms(&mut self, ms: u32) {
+            self.0 += ms;
+        }
+    }
+
+    /// Tests that embedded-hal delays wait the requested time.
+    #[test]
+    fn test_hal_delay() {
+        let mut delay = Recorder(0);
+        delay.wait_ms(20, 48_000);
+        delay.wait_ms(5, 0);
+        assert_eq!(delay.0, 25);
+        BusyWait.wait_ms(u32::MAX, u32::MAX);
+    }
+}
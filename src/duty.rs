@@ -0,0 +1,66 @@
+//! Duty cycle types supported by the effects
+//!
+//! The effect math works on `u32`. [`DutyValue`] spells out how a pin's
+//! duty type converts to and from it, and is sealed so that only types
+//! with well-defined conversions can be used with `LEDEffect`:
+//!
+//! * `u32` converts losslessly both ways;
+//! * `u16`, the usual duty type of 16-bit timers, widens losslessly and
+//!   never receives a value above the pin's maximum from the effects;
+//! * `u8` works the same way for 8-bit timers. Conversions from `u32`
+//!   saturate at `u8::MAX`, so an out-of-range value can never wrap
+//!   around to a dim level.
+//!
+//! A generic `From<u32>` bound would also admit types whose conversion
+//! truncates silently, corrupting duty values in the effect math.
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// A PWM duty type that the effects can compute with
+///
+/// Implemented for `u8`, `u16` and `u32` only; see the module documentation.
+pub trait DutyValue: private::Sealed + Into<u32> + Copy + Ord {
+    /// Convert an effect value back to the duty type, saturating at its maximum
+    fn from_u32(value: u32) -> Self;
+}
+
+impl DutyValue for u8 {
+    #[inline(always)]
+    fn from_u32(value: u32) -> Self {
+        value.min(u32::from(u8::MAX)) as u8
+    }
+}
+
+impl DutyValue for u16 {
+    #[inline(always)]
+    fn from_u32(value: u32) -> Self {
+        value.min(u32::from(u16::MAX)) as u16
+    }
+}
+
+impl DutyValue for u32 {
+    #[inline(always)]
+    fn from_u32(value: u32) -> Self {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that conversions from `u32` saturate instead of wrapping.
+    #[test]
+    fn test_from_u32_saturates() {
+        assert_eq!(u8::from_u32(200), 200);
+        assert_eq!(u8::from_u32(256), u8::MAX);
+        assert_eq!(u16::from_u32(70_000), u16::MAX);
+        assert_eq!(u32::from_u32(u32::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,96 @@
+//! Easing curves for fades
+//!
+//! An [`Easing`] reshapes the progress of a fade (see
+//! `LEDEffect::fade_eased`). Progress is fixed-point Q16: `0` is the start
+//! and `65536` the end of the fade. Some curves leave `0..=65536` on the
+//! way, overshooting the target like a spring; the fade clamps the
+//! resulting duties into the configured range.
+
+#[cfg(feature = "defmt")]
+use defmt::Format;
+
+/// Fixed-point `1.0` in Q16
+pub const ONE: i32 = 1 << 16;
+
+/// Overshoot constant of `EaseOutBack` (1.70158) in Q16
+const BACK_C1: i64 = 111_514;
+
+/// `BACK_C1 + 1` in Q16
+const BACK_C3: i64 = BACK_C1 + ONE as i64;
+
+/// Parabola steepness of `EaseOutBounce` (7.5625) in Q16
+const BOUNCE_N1: i64 = 495_616;
+
+/// Shape of a fade's progress over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub enum Easing {
+    /// Constant speed from start to end
+    #[default]
+    Linear,
+    /// Overshoot the target by about 10% and settle back, like a spring
+    EaseOutBack,
+    /// Reach the target and bounce back off it three times, decreasing
+    EaseOutBounce,
+}
+
+impl Easing {
+    /// Map linear progress `t` (Q16, `0..=65536`) to eased progress
+    ///
+    /// Every curve starts at `0` and ends at exactly `65536`; in between the
+    /// result may leave that range (see the variant docs).
+    pub fn apply(self, t: u32) -> i32 {
+        let t = i64::from(t.min(ONE as u32));
+        let eased = match self {
+            Easing::Linear => t,
+            Easing::EaseOutBack => {
+                let u = t - i64::from(ONE);
+                let u2 = (u * u) >> 16;
+                let u3 = (u2 * u) >> 16;
+                i64::from(ONE) + ((BACK_C3 * u3) >> 16) + ((BACK_C1 * u2) >> 16)
+            }
+            Easing::EaseOutBounce => {
+                let parabola = |x: i64, lift: i64| ((BOUNCE_N1 * ((x * x) >> 16)) >> 16) + lift;
+                if t < 23_831 {
+                    parabola(t, 0)
+                } else if t < 47_663 {
+                    parabola(t - 35_747, 49_152)
+                } else if t < 59_578 {
+                    parabola(t - 53_620, 61_440)
+                } else {
+                    parabola(t - 62_557, 64_512)
+                }
+            }
+        };
+        if t == i64::from(ONE) {
+            ONE
+        } else {
+            eased as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the endpoints and the overshoot/undershoot of each curve.
+    #[test]
+    fn test_easing_shapes() {
+        for easing in [Easing::Linear, Easing::EaseOutBack, Easing::EaseOutBounce] {
+            assert_eq!(easing.apply(0), 0);
+            assert_eq!(easing.apply(ONE as u32), ONE);
+        }
+        assert_eq!(Easing::Linear.apply(16_384), 16_384);
+
+        let back = (0..=ONE as u32).step_by(256).map(|t| Easing::EaseOutBack.apply(t));
+        let peak = back.max().unwrap();
+        assert!(peak > ONE + ONE / 20 && peak < ONE + ONE / 8, "{}", peak);
+
+        // The bounce touches the target early, then dips back below it.
+        let bounce = |t| Easing::EaseOutBounce.apply(t);
+        assert!((ONE - 100..=ONE).contains(&bounce(23_831)));
+        assert!(bounce(35_747) < ONE * 4 / 5);
+        assert!((0..=ONE as u32).step_by(64).all(|t| (0..=ONE).contains(&bounce(t))));
+    }
+}
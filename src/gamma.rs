@@ -0,0 +1,209 @@
+//! Perceptual brightness correction
+//!
+//! The eye responds to light roughly logarithmically, so a linear duty ramp
+//! looks like it races through the dark end and barely changes near the top.
+//! The helpers here map a perceptual level in `0..=65535` onto a linear
+//! intensity in the same range using a gamma curve. With the `float-math`
+//! feature the curve is evaluated with `f32`/`libm` rather than interpolated
+//! from [`GAMMA_2_2`]. Curves with another gamma or size can be baked into
+//! flash at compile time with [`table`] or the
+//! [`gamma_table!`](crate::gamma_table) macro.
+
+/// Gamma 2.2 curve sampled at 256 evenly spaced perceptual levels
+///
+/// Entry `i` is `65535 * (i / 255)^2.2`, rounded.
+pub const GAMMA_2_2: [u16; 256] = [
+    0, 0, 2, 4, 7, 11, 17, 24, 32, 42, 53, 65,
+    79, 94, 111, 129, 148, 169, 192, 216, 242, 270, 299, 330,
+    362, 396, 432, 469, 508, 549, 591, 635, 681, 729, 779, 830,
+    883, 938, 995, 1053, 1113, 1175, 1239, 1305, 1373, 1443, 1514, 1587,
+    1663, 1740, 1819, 1900, 1983, 2068, 2155, 2243, 2334, 2427, 2521, 2618,
+    2717, 2817, 2920, 3024, 3131, 3240, 3350, 3463, 3578, 3694, 3813, 3934,
+    4057, 4182, 4309, 4438, 4570, 4703, 4838, 4976, 5115, 5257, 5401, 5547,
+    5695, 5845, 5998, 6152, 6309, 6468, 6629, 6792, 6957, 7124, 7294, 7466,
+    7640, 7816, 7994, 8175, 8358, 8543, 8730, 8919, 9111, 9305, 9501, 9699,
+    9900, 10102, 10307, 10515, 10724, 10936, 11150, 11366, 11585, 11806, 12029, 12254,
+    12482, 12712, 12944, 13179, 13416, 13655, 13896, 14140, 14386, 14635, 14885, 15138,
+    15394, 15652, 15912, 16174, 16439, 16706, 16975, 17247, 17521, 17798, 18077, 18358,
+    18642, 18928, 19216, 19507, 19800, 20095, 20393, 20694, 20996, 21301, 21609, 21919,
+    22231, 22546, 22863, 23182, 23504, 23829, 24156, 24485, 24817, 25151, 25487, 25826,
+    26168, 26512, 26858, 27207, 27558, 27912, 28268, 28627, 28988, 29351, 29717, 30086,
+    30457, 30830, 31206, 31585, 31966, 32349, 32735, 33124, 33514, 33908, 34304, 34702,
+    35103, 35507, 35913, 36321, 36732, 37146, 37562, 37981, 38402, 38825, 39252, 39680,
+    40112, 40546, 40982, 41421, 41862, 42306, 42753, 43202, 43654, 44108, 44565, 45025,
+    45487, 45951, 46418, 46888, 47360, 47835, 48313, 48793, 49275, 49761, 50249, 50739,
+    51232, 51728, 52226, 52727, 53230, 53736, 54245, 54756, 55270, 55787, 56306, 56828,
+    57352, 57879, 58409, 58941, 59476, 60014, 60554, 61097, 61642, 62190, 62741, 63295,
+    63851, 64410, 64971, 65535,
+];
+
+/// Map a perceptual `level` onto linear intensity with a gamma of 2.2
+///
+/// Both input and output span `0..=65535`; `0` and `65535` map to themselves.
+#[cfg(feature = "float-math")]
+pub fn correct(level: u16) -> u16 {
+    let normalized = f32::from(level) / f32::from(u16::MAX);
+    libm::roundf(libm::powf(normalized, 2.2) * f32::from(u16::MAX)) as u16
+}
+
+/// Map a perceptual `level` onto linear intensity using [`GAMMA_2_2`]
+///
+/// Both input and output span `0..=65535`. Values between table entries are
+/// linearly interpolated; `0` and `65535` map to themselves.
+#[cfg(not(feature = "float-math"))]
+pub fn correct(level: u16) -> u16 {
+    correct_lut(level)
+}
+
+/// Table-based gamma correction usable in `const` contexts
+///
+/// Behaves like [`correct`] without the `float-math` feature, interpolating
+/// [`GAMMA_2_2`] regardless of the features enabled, so it can bake gamma
+/// into tables generated at compile time.
+pub const fn correct_lut(level: u16) -> u16 {
+    correct_with(&GAMMA_2_2, level)
+}
+
+/// Map a perceptual `level` onto linear intensity using a custom curve
+///
+/// `table` samples the curve at 256 evenly spaced levels like
+/// [`GAMMA_2_2`] and must be non-decreasing; values in between are linearly
+/// interpolated. Lets each LED colour use a curve of its own (see
+/// `RgbEffect::set_gamma_tables`).
+pub const fn correct_with(table: &[u16; 256], level: u16) -> u16 {
+    let index = (level >> 8) as usize;
+    let next = if index + 1 < table.len() { index + 1 } else { index };
+    let frac = (level & 0xFF) as u32;
+    let a = table[index] as u32;
+    let b = table[next] as u32;
+    (a + b.saturating_sub(a) * frac / 256) as u16
+}
+
+/// Sample the curve `x^gamma` at `N` evenly spaced levels, at compile time
+///
+/// Entry `i` is `65535 * (i / (N - 1))^gamma`, rounded, so `table::<256>(2.2)`
+/// reproduces [`GAMMA_2_2`]. Meant for `const` and `static` items (see
+/// [`gamma_table!`](crate::gamma_table)), where the floating-point math runs
+/// in the compiler and only the finished table ends up in flash. `gamma`
+/// must be positive; a single-entry table is `[65535]`.
+pub const fn table<const N: usize>(gamma: f64) -> [u16; N] {
+    let mut table = [u16::MAX; N];
+    if N < 2 {
+        return table;
+    }
+
+    let last = (N - 1) as f64;
+    let mut i = 0;
+    while i < N - 1 {
+        let value = if i == 0 { 0.0 } else { exp(gamma * ln(i as f64 / last)) };
+        table[i] = (value * u16::MAX as f64 + 0.5) as u16;
+        i += 1;
+    }
+    table
+}
+
+/// Natural logarithm of `x` in `(0, 1]`, for [`table`].
+const fn ln(mut x: f64) -> f64 {
+    // Scale into [0.5, 1], then sum the series of 2 * atanh((x - 1) / (x + 1)).
+    let mut halvings = 0.0;
+    while x < 0.5 {
+        x *= 2.0;
+        halvings += 1.0;
+    }
+    let z = (x - 1.0) / (x + 1.0);
+    let (mut term, mut sum, mut k) = (z, 0.0, 1.0);
+    while k < 60.0 {
+        sum += term / k;
+        term *= z * z;
+        k += 2.0;
+    }
+    2.0 * sum - halvings * core::f64::consts::LN_2
+}
+
+/// `e^y` for `y <= 0`, for [`table`].
+const fn exp(mut y: f64) -> f64 {
+    // Shift into [-ln 2, 0], sum the Taylor series, then halve back.
+    let mut halvings = 0;
+    while y < -core::f64::consts::LN_2 {
+        y += core::f64::consts::LN_2;
+        halvings += 1;
+    }
+    let (mut term, mut sum, mut k) = (1.0, 1.0, 1.0);
+    while k < 30.0 {
+        term *= y / k;
+        sum += term;
+        k += 1.0;
+    }
+    while halvings > 0 {
+        sum /= 2.0;
+        halvings -= 1;
+    }
+    sum
+}
+
+/// Perceptual level, on the 256 levels of [`GAMMA_2_2`], of a linear intensity
+///
+/// The inverse of [`correct_lut`] at table resolution: returns the highest
+/// level whose intensity does not exceed `linear`. Two intensities that map
+/// to the same level look the same to the eye.
+pub const fn perceived(linear: u16) -> u8 {
+    let (mut low, mut high) = (0, GAMMA_2_2.len() - 1);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if GAMMA_2_2[mid] <= linear {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the curve keeps its endpoints and is monotonic.
+    #[test]
+    fn test_correct_endpoints_and_monotonic() {
+        assert_eq!(correct(0), 0);
+        assert_eq!(correct(u16::MAX), u16::MAX);
+
+        let mut previous = 0;
+        for level in (0..=u16::MAX).step_by(61) {
+            let value = correct(level);
+            assert!(value >= previous);
+            previous = value;
+        }
+    }
+
+    /// Tests that half perceptual brightness is far below half intensity.
+    #[test]
+    fn test_correct_midpoint() {
+        // 0.5^2.2 ≈ 0.2176
+        let mid = correct(32768);
+        assert!((14_000..=14_400).contains(&mid), "{}", mid);
+    }
+
+    /// Tests that `perceived` inverts the table at its sample points.
+    #[test]
+    fn test_perceived() {
+        assert_eq!(perceived(u16::MAX), 255);
+        assert_eq!(perceived(GAMMA_2_2[128]), 128);
+        assert_eq!(perceived(GAMMA_2_2[128] - 1), 127);
+        // The same duty change is many levels at the bottom, few at the top.
+        assert!(perceived(2_000) - perceived(1_000) > perceived(65_000) - perceived(64_000));
+    }
+
+    /// Tests that compile-time tables reproduce the built-in curve.
+    #[test]
+    fn test_table() {
+        const TABLE: [u16; 256] = table(2.2);
+        assert_eq!(TABLE, GAMMA_2_2);
+
+        const SMALL: [u16; 5] = crate::gamma_table!(gamma = 2.0, size = 5);
+        assert_eq!(SMALL, [0, 4_096, 16_384, 36_863, 65_535]);
+        assert_eq!(table::<3>(1.0), [0, 32_768, 65_535]);
+        assert_eq!(table::<1>(2.2), [65_535]);
+    }
+}
@@ -0,0 +1,141 @@
+//! Driving several pins with one effect
+//!
+//! A [`Group`] runs a single `LEDEffect` on a fixed-size array of pins, so a
+//! panel of indicators breathes or fades in lockstep. Each pin has its own
+//! brightness offset, added to every lit duty before it is written, which
+//! evens out LEDs of different efficiency or sets deliberate differences.
+
+use embedded_hal::PwmPin;
+
+use crate::{DutyValue, Error, LEDEffect};
+
+/// The pins of a `Group`, presented to `LEDEffect` as a single `PwmPin`
+///
+/// Writing a duty writes it to every pin, shifted by that pin's offset and
+/// clamped into `0..=get_max_duty()`; the "off" duty `0` turns every pin
+/// off regardless of its offset. Reading the duty returns the last value
+/// written to the group, before offsets.
+pub struct GroupPins<P, const N: usize>
+where
+    P: PwmPin,
+{
+    pins: [P; N],
+    offsets: [i32; N],
+    duty: P::Duty,
+}
+
+impl<P, const N: usize> PwmPin for GroupPins<P, N>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    type Duty = P::Duty;
+
+    fn disable(&mut self) {
+        self.pins.iter_mut().for_each(|pin| pin.disable());
+    }
+
+    fn enable(&mut self) {
+        self.pins.iter_mut().for_each(|pin| pin.enable());
+    }
+
+    fn get_duty(&self) -> Self::Duty {
+        self.duty
+    }
+
+    fn get_max_duty(&self) -> Self::Duty {
+        self.pins
+            .iter()
+            .map(|pin| pin.get_max_duty())
+            .min()
+            .unwrap_or(DutyValue::from_u32(0))
+    }
+
+    fn set_duty(&mut self, duty: Self::Duty) {
+        self.duty = duty;
+        let value = i64::from(duty.into());
+        for (pin, &offset) in self.pins.iter_mut().zip(self.offsets.iter()) {
+            let shifted = if value == 0 {
+                0
+            } else {
+                (value + i64::from(offset)).clamp(0, i64::from(pin.get_max_duty().into()))
+            };
+            pin.set_duty(DutyValue::from_u32(shifted as u32));
+        }
+    }
+}
+
+/// `N` pins driven in lockstep by one effect, each with a brightness offset
+///
+/// ```ignore
+/// let mut panel = Group::new([ch1, ch2, ch3], [0, -20, 15], 5, max)?;
+/// panel.breath(4000)?;
+/// panel.effect().set_end_state(EndState::Min);
+/// panel.effect().heartbeat(2, 1, 60)?;
+/// ```
+pub struct Group<'a, P, const N: usize>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    led: LEDEffect<'a, GroupPins<P, N>>,
+}
+
+impl<'a, P, const N: usize> Group<'a, P, N>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    /// Group `pins` with the given per-pin duty `offsets`
+    ///
+    /// `pwm_min` and `pwm_max` describe the shared effect range, checked as
+    /// in `LEDEffect::new`.
+    pub fn new(
+        pins: [P; N],
+        offsets: [i32; N],
+        pwm_min: P::Duty,
+        pwm_max: P::Duty,
+    ) -> Result<Self, Error> {
+        let duty = pins
+            .first()
+            .map_or(DutyValue::from_u32(0), |pin| pin.get_duty());
+        let pins = GroupPins { pins, offsets, duty };
+        Ok(Self {
+            led: LEDEffect::new(pins, pwm_min, pwm_max)?,
+        })
+    }
+
+    /// Run `LEDEffect::breath` on all pins at once
+    pub fn breath(&mut self, duration: u32) -> Result<(), Error> {
+        self.led.breath(duration)
+    }
+
+    /// Run `LEDEffect::fade` on all pins at once
+    pub fn fade(&mut self, target: P::Duty, duration_ms: u32) -> Result<(), Error> {
+        self.led.fade(target, duration_ms)
+    }
+
+    /// The shared effect, for its settings and the remaining effects
+    pub fn effect(&mut self) -> &mut LEDEffect<'a, GroupPins<P, N>> {
+        &mut self.led
+    }
+
+    /// Per-pin duty offsets, in pin order
+    pub fn offsets(&self) -> [i32; N] {
+        self.led.pin.offsets
+    }
+
+    /// Change the duty offset of pin `index`, taking effect with the next write
+    ///
+    /// Returns `Error::InvalidParameter` if `index` is out of bounds.
+    pub fn set_offset(&mut self, index: usize, offset: i32) -> Result<(), Error> {
+        let slot = self.led.pin.offsets.get_mut(index).ok_or(Error::InvalidParameter)?;
+        *slot = offset;
+        Ok(())
+    }
+
+    /// Destroy the group and return the pins
+    pub fn destroy(self) -> [P; N] {
+        self.led.destroy().pins
+    }
+}
@@ -0,0 +1,68 @@
+//! Grouping of heartbeat beats
+//!
+//! `LEDEffect::heartbeat` flashes its beats in groups of `grouped_as`: the
+//! beats of a group follow each other at the normal pace, and the last beat
+//! of each group is followed by a longer pause, so the beats read as
+//! "ba-dum ... ba-dum". The flash and decay of a beat take the same time
+//! whatever the grouping; only the gap after it depends on it. With
+//! `period_time` a sixth of `60_000 / bpm` ms:
+//!
+//! * `grouped_as = 0`: no groups, every gap is `period_time`;
+//! * `grouped_as = 1`: every beat is a group of its own and every gap is
+//!   `2 * period_time`, a steady, slower rhythm;
+//! * `grouped_as = 3`: beats 1 and 2 are followed by `period_time`, beat 3
+//!   by `(2 + 3) * period_time`, then beats 4 and 5 by `period_time`, and
+//!   so on.
+//!
+//! A group of one has no short gaps to contrast with, so it only gets the
+//! doubled gap, without the pause that grows with the group size.
+
+/// The gap after beat `n` of a heartbeat, in milliseconds
+///
+/// `n` is the 1-based number of the beat and `period_time` the sixth of a
+/// beat in milliseconds. See the module documentation for the meaning of
+/// `grouped_as`. Saturates at `u32::MAX` instead of overflowing.
+#[inline(always)]
+pub const fn wait(n: u32, grouped_as: u32, period_time: u32) -> u32 {
+    if !n.is_multiple_of(grouped_as) {
+        period_time
+    } else if grouped_as == 1 {
+        period_time.saturating_mul(2)
+    } else {
+        period_time
+            .saturating_mul(2)
+            .saturating_add(grouped_as.saturating_mul(period_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the gaps against a table of group sizes and beat numbers.
+    #[test]
+    fn test_wait_table() {
+        // (grouped_as, gaps after beats 1..=6) with a period time of 100 ms
+        const TABLE: [(u32, [u32; 6]); 5] = [
+            (0, [100, 100, 100, 100, 100, 100]),
+            (1, [200, 200, 200, 200, 200, 200]),
+            (2, [100, 400, 100, 400, 100, 400]),
+            (3, [100, 100, 500, 100, 100, 500]),
+            (7, [100, 100, 100, 100, 100, 100]),
+        ];
+        for (grouped_as, gaps) in TABLE {
+            for (n, gap) in (1..).zip(gaps) {
+                assert_eq!(wait(n, grouped_as, 100), gap, "beat {} of {}", n, grouped_as);
+            }
+        }
+        assert_eq!(wait(7, 7, 100), 900);
+    }
+
+    /// Tests that extreme parameters saturate instead of overflowing.
+    #[test]
+    fn test_wait_saturates() {
+        assert_eq!(wait(1, 1, u32::MAX), u32::MAX);
+        assert_eq!(wait(u32::MAX, u32::MAX, 2), u32::MAX);
+        assert_eq!(wait(3, 3, 0), 0);
+    }
+}
@@ -0,0 +1,474 @@
+//! Storable descriptions of repeatable effects
+//!
+//! An [`EffectKind`] names one of the `LEDEffect` effects together with the
+//! parameters it runs with, so "which effect" can be stored and changed at
+//! runtime (see `LEDEffect::run_kind`) and chosen separately from "for how
+//! long" (see `LEDEffect::run_for`).
+//!
+//! [`EFFECTS`] lists every kind with a display name and the range of each
+//! parameter, so on-device menus can be built from it instead of a
+//! hand-maintained copy:
+//!
+//! ```ignore
+//! for info in EFFECTS {
+//!     menu.add(info.name);
+//! }
+//! let chosen = &EFFECTS[menu.selected()];
+//! led.run_kind(chosen.kind([3_000, 0, 0])?)?;
+//! ```
+
+#[cfg(feature = "defmt")]
+use defmt::Format;
+
+use crate::config::{
+    EFFECT_BLINK, EFFECT_BREATH, EFFECT_COUNTDOWN, EFFECT_CYCLE, EFFECT_DISTRESS, EFFECT_FADE,
+    EFFECT_FAULT_CODE, EFFECT_HEARTBEAT, EFFECT_HEARTBEAT_REALISTIC, EFFECT_LAYERED_BREATH,
+    EFFECT_ORGANIC_BREATH, EFFECT_SPARKLE, EFFECT_STAIRCASE, EFFECT_THROB,
+};
+use crate::Error;
+
+/// A repeatable effect and its parameters
+///
+/// Brightness levels (`from`, `to`, `small_low`) span `0..=65535` across
+/// `[pwm_min, pwm_max]` (see `LEDEffect::duty_from_fraction`), so a stored
+/// kind means the same on any pin. Effects that take an RNG carry a seed
+/// for a small internal generator instead; the same seed always gives the
+/// same pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub enum EffectKind {
+    /// A breathing cycle (see `LEDEffect::breath`)
+    Breath {
+        /// Nominal duration of the breathing cycle in milliseconds
+        duration: u32,
+    },
+    /// A heartbeat (see `LEDEffect::heartbeat`)
+    Heartbeat {
+        /// Number of beats to flash
+        flash_beats: u32,
+        /// Number of beats per group
+        grouped_as: u32,
+        /// Beats per minute
+        bpm: u32,
+    },
+    /// A brightness staircase (see `LEDEffect::staircase`)
+    Staircase {
+        /// Number of evenly spaced levels
+        levels: u32,
+        /// Time to hold each level in milliseconds
+        hold_ms: u32,
+    },
+    /// A fade between two levels (see `LEDEffect::fade_between`)
+    Fade {
+        /// Level to start from, `0..=65535`
+        from: u32,
+        /// Level to end on, `0..=65535`
+        to: u32,
+        /// Length of the fade in milliseconds
+        duration_ms: u32,
+    },
+    /// Plain blinking (see `LEDEffect::blink`)
+    Blink {
+        /// Time lit in milliseconds
+        on_ms: u32,
+        /// Time dark in milliseconds
+        off_ms: u32,
+        /// Number of blinks
+        times: u32,
+    },
+    /// A fast rise and slow decay (see `LEDEffect::throb`)
+    Throb {
+        /// Rise time in milliseconds
+        attack_ms: u32,
+        /// Decay time in milliseconds
+        decay_ms: u32,
+        /// Number of cycles
+        cycles: u32,
+    },
+    /// A one-directional brightness loop (see `LEDEffect::cycle`)
+    Cycle {
+        /// Length of one cycle in milliseconds
+        period_ms: u32,
+        /// Number of cycles
+        cycles: u32,
+    },
+    /// A dimming countdown timer (see `LEDEffect::countdown`)
+    Countdown {
+        /// Length of the countdown in milliseconds
+        total_ms: u32,
+    },
+    /// An ECG-like heartbeat (see `LEDEffect::heartbeat_realistic`)
+    HeartbeatRealistic {
+        /// Beats per minute
+        bpm: u32,
+        /// Number of beats
+        beats: u32,
+    },
+    /// Shallow breaths followed by deep ones (see `LEDEffect::layered_breath`)
+    LayeredBreath {
+        /// Number of shallow breaths
+        small_cycles: u32,
+        /// Level the shallow breaths dip to, `0..=65535`
+        small_low: u32,
+        /// Number of full-range breaths
+        big_cycles: u32,
+    },
+    /// Random flashes (see `LEDEffect::sparkle`)
+    Sparkle {
+        /// Length of the effect in milliseconds
+        duration_ms: u32,
+        /// Chance of a flash per slice, `0..=255`
+        density: u32,
+        /// Seed for the flashes
+        seed: u32,
+    },
+    /// Breathing with random variation (see `LEDEffect::organic_breath`)
+    OrganicBreath {
+        /// Length of the effect in milliseconds
+        duration_ms: u32,
+        /// Seed for the variation
+        seed: u32,
+    },
+    /// A blinked error code (see `LEDEffect::fault_code`)
+    FaultCode {
+        /// Number of blinks per group, `1..=255`
+        code: u32,
+        /// Number of groups, `0` for forever
+        repeats: u32,
+    },
+    /// The SOS signal (see `LEDEffect::distress`)
+    Distress {
+        /// Number of times to send the pattern
+        repeats: u32,
+    },
+}
+
+impl EffectKind {
+    /// The `config::EFFECT_*` number identifying this kind of effect
+    pub const fn code(&self) -> u32 {
+        match self {
+            EffectKind::Breath { .. } => EFFECT_BREATH,
+            EffectKind::Heartbeat { .. } => EFFECT_HEARTBEAT,
+            EffectKind::Staircase { .. } => EFFECT_STAIRCASE,
+            EffectKind::Fade { .. } => EFFECT_FADE,
+            EffectKind::Blink { .. } => EFFECT_BLINK,
+            EffectKind::Throb { .. } => EFFECT_THROB,
+            EffectKind::Cycle { .. } => EFFECT_CYCLE,
+            EffectKind::Countdown { .. } => EFFECT_COUNTDOWN,
+            EffectKind::HeartbeatRealistic { .. } => EFFECT_HEARTBEAT_REALISTIC,
+            EffectKind::LayeredBreath { .. } => EFFECT_LAYERED_BREATH,
+            EffectKind::Sparkle { .. } => EFFECT_SPARKLE,
+            EffectKind::OrganicBreath { .. } => EFFECT_ORGANIC_BREATH,
+            EffectKind::FaultCode { .. } => EFFECT_FAULT_CODE,
+            EffectKind::Distress { .. } => EFFECT_DISTRESS,
+        }
+    }
+
+    /// The parameters of this effect in declaration order, padded with zeros
+    ///
+    /// The inverse of `from_code`.
+    pub const fn params(&self) -> [u32; 3] {
+        match *self {
+            EffectKind::Breath { duration } => [duration, 0, 0],
+            EffectKind::Heartbeat {
+                flash_beats,
+                grouped_as,
+                bpm,
+            } => [flash_beats, grouped_as, bpm],
+            EffectKind::Staircase { levels, hold_ms } => [levels, hold_ms, 0],
+            EffectKind::Fade {
+                from,
+                to,
+                duration_ms,
+            } => [from, to, duration_ms],
+            EffectKind::Blink {
+                on_ms,
+                off_ms,
+                times,
+            } => [on_ms, off_ms, times],
+            EffectKind::Throb {
+                attack_ms,
+                decay_ms,
+                cycles,
+            } => [attack_ms, decay_ms, cycles],
+            EffectKind::Cycle { period_ms, cycles } => [period_ms, cycles, 0],
+            EffectKind::Countdown { total_ms } => [total_ms, 0, 0],
+            EffectKind::HeartbeatRealistic { bpm, beats } => [bpm, beats, 0],
+            EffectKind::LayeredBreath {
+                small_cycles,
+                small_low,
+                big_cycles,
+            } => [small_cycles, small_low, big_cycles],
+            EffectKind::Sparkle {
+                duration_ms,
+                density,
+                seed,
+            } => [duration_ms, density, seed],
+            EffectKind::OrganicBreath { duration_ms, seed } => [duration_ms, seed, 0],
+            EffectKind::FaultCode { code, repeats } => [code, repeats, 0],
+            EffectKind::Distress { repeats } => [repeats, 0, 0],
+        }
+    }
+
+    /// Build the effect numbered `code` from its parameters in declaration order
+    ///
+    /// Unused trailing parameters are ignored. Returns `None` for a `code`
+    /// that names no effect.
+    pub const fn from_code(code: u32, params: [u32; 3]) -> Option<EffectKind> {
+        let [a, b, c] = params;
+        match code {
+            EFFECT_BREATH => Some(EffectKind::Breath { duration: a }),
+            EFFECT_HEARTBEAT => Some(EffectKind::Heartbeat {
+                flash_beats: a,
+                grouped_as: b,
+                bpm: c,
+            }),
+            EFFECT_STAIRCASE => Some(EffectKind::Staircase {
+                levels: a,
+                hold_ms: b,
+            }),
+            EFFECT_FADE => Some(EffectKind::Fade {
+                from: a,
+                to: b,
+                duration_ms: c,
+            }),
+            EFFECT_BLINK => Some(EffectKind::Blink {
+                on_ms: a,
+                off_ms: b,
+                times: c,
+            }),
+            EFFECT_THROB => Some(EffectKind::Throb {
+                attack_ms: a,
+                decay_ms: b,
+                cycles: c,
+            }),
+            EFFECT_CYCLE => Some(EffectKind::Cycle {
+                period_ms: a,
+                cycles: b,
+            }),
+            EFFECT_COUNTDOWN => Some(EffectKind::Countdown { total_ms: a }),
+            EFFECT_HEARTBEAT_REALISTIC => Some(EffectKind::HeartbeatRealistic {
+                bpm: a,
+                beats: b,
+            }),
+            EFFECT_LAYERED_BREATH => Some(EffectKind::LayeredBreath {
+                small_cycles: a,
+                small_low: b,
+                big_cycles: c,
+            }),
+            EFFECT_SPARKLE => Some(EffectKind::Sparkle {
+                duration_ms: a,
+                density: b,
+                seed: c,
+            }),
+            EFFECT_ORGANIC_BREATH => Some(EffectKind::OrganicBreath {
+                duration_ms: a,
+                seed: b,
+            }),
+            EFFECT_FAULT_CODE => Some(EffectKind::FaultCode {
+                code: a,
+                repeats: b,
+            }),
+            EFFECT_DISTRESS => Some(EffectKind::Distress { repeats: a }),
+            _ => None,
+        }
+    }
+}
+
+/// Name and range of one effect parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub struct ParamInfo {
+    /// Human-readable name, suitable for a menu
+    pub name: &'static str,
+    /// Smallest value to offer
+    pub min: u32,
+    /// Largest value to offer
+    pub max: u32,
+}
+
+/// Description of one kind of effect, for building menus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub struct EffectInfo {
+    /// Human-readable name, suitable for a menu
+    pub name: &'static str,
+    /// The `config::EFFECT_*` number, as returned by `EffectKind::code`
+    pub code: u32,
+    /// The parameters of the effect, in declaration order
+    pub params: &'static [ParamInfo],
+}
+
+impl EffectInfo {
+    /// Build the effect from `params`, checking each against its range
+    ///
+    /// Parameters beyond `self.params.len()` are ignored. Returns
+    /// `Error::InvalidParameter` if any parameter is out of range.
+    pub fn kind(&self, params: [u32; 3]) -> Result<EffectKind, Error> {
+        for (info, &value) in self.params.iter().zip(params.iter()) {
+            if value < info.min || value > info.max {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        EffectKind::from_code(self.code, params).ok_or(Error::InvalidParameter)
+    }
+}
+
+/// Every kind of `EffectKind`, in declaration order
+///
+/// The ranges are the values worth offering to a user; the effects
+/// themselves accept wider ones (see each method for what it rejects).
+pub const EFFECTS: &[EffectInfo] = &[
+    EffectInfo {
+        name: "Breath",
+        code: EFFECT_BREATH,
+        params: &[ParamInfo { name: "Duration (ms)", min: 300, max: 60_000 }],
+    },
+    EffectInfo {
+        name: "Heartbeat",
+        code: EFFECT_HEARTBEAT,
+        params: &[
+            ParamInfo { name: "Beats", min: 1, max: 100 },
+            ParamInfo { name: "Beats per group", min: 0, max: 8 },
+            ParamInfo { name: "BPM", min: 20, max: 240 },
+        ],
+    },
+    EffectInfo {
+        name: "Staircase",
+        code: EFFECT_STAIRCASE,
+        params: &[
+            ParamInfo { name: "Levels", min: 1, max: 256 },
+            ParamInfo { name: "Hold (ms)", min: 0, max: 10_000 },
+        ],
+    },
+    EffectInfo {
+        name: "Fade",
+        code: EFFECT_FADE,
+        params: &[
+            ParamInfo { name: "From", min: 0, max: 65_535 },
+            ParamInfo { name: "To", min: 0, max: 65_535 },
+            ParamInfo { name: "Duration (ms)", min: 0, max: 60_000 },
+        ],
+    },
+    EffectInfo {
+        name: "Blink",
+        code: EFFECT_BLINK,
+        params: &[
+            ParamInfo { name: "On (ms)", min: 10, max: 10_000 },
+            ParamInfo { name: "Off (ms)", min: 10, max: 10_000 },
+            ParamInfo { name: "Times", min: 1, max: 100 },
+        ],
+    },
+    EffectInfo {
+        name: "Throb",
+        code: EFFECT_THROB,
+        params: &[
+            ParamInfo { name: "Attack (ms)", min: 10, max: 10_000 },
+            ParamInfo { name: "Decay (ms)", min: 10, max: 10_000 },
+            ParamInfo { name: "Cycles", min: 1, max: 100 },
+        ],
+    },
+    EffectInfo {
+        name: "Cycle",
+        code: EFFECT_CYCLE,
+        params: &[
+            ParamInfo { name: "Period (ms)", min: 100, max: 60_000 },
+            ParamInfo { name: "Cycles", min: 1, max: 100 },
+        ],
+    },
+    EffectInfo {
+        name: "Countdown",
+        code: EFFECT_COUNTDOWN,
+        params: &[ParamInfo { name: "Duration (ms)", min: 1_000, max: 3_600_000 }],
+    },
+    EffectInfo {
+        name: "Realistic heartbeat",
+        code: EFFECT_HEARTBEAT_REALISTIC,
+        params: &[
+            ParamInfo { name: "BPM", min: 20, max: 240 },
+            ParamInfo { name: "Beats", min: 1, max: 100 },
+        ],
+    },
+    EffectInfo {
+        name: "Layered breath",
+        code: EFFECT_LAYERED_BREATH,
+        params: &[
+            ParamInfo { name: "Shallow breaths", min: 0, max: 10 },
+            ParamInfo { name: "Shallow low", min: 1_000, max: 64_000 },
+            ParamInfo { name: "Deep breaths", min: 0, max: 10 },
+        ],
+    },
+    EffectInfo {
+        name: "Sparkle",
+        code: EFFECT_SPARKLE,
+        params: &[
+            ParamInfo { name: "Duration (ms)", min: 100, max: 60_000 },
+            ParamInfo { name: "Density", min: 0, max: 255 },
+            ParamInfo { name: "Seed", min: 0, max: u32::MAX },
+        ],
+    },
+    EffectInfo {
+        name: "Organic breath",
+        code: EFFECT_ORGANIC_BREATH,
+        params: &[
+            ParamInfo { name: "Duration (ms)", min: 4_000, max: 600_000 },
+            ParamInfo { name: "Seed", min: 0, max: u32::MAX },
+        ],
+    },
+    EffectInfo {
+        name: "Fault code",
+        code: EFFECT_FAULT_CODE,
+        params: &[
+            ParamInfo { name: "Code", min: 1, max: 9 },
+            ParamInfo { name: "Repeats (0 = forever)", min: 0, max: 10 },
+        ],
+    },
+    EffectInfo {
+        name: "Distress",
+        code: EFFECT_DISTRESS,
+        params: &[ParamInfo { name: "Repeats", min: 1, max: 10 }],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the effect list matches the kinds it describes.
+    #[test]
+    fn test_effects_list() {
+        for (index, info) in EFFECTS.iter().enumerate() {
+            let mins: [u32; 3] =
+                core::array::from_fn(|i| info.params.get(i).map_or(0, |param| param.min));
+            let kind = info.kind(mins).unwrap();
+            assert_eq!(kind.code(), info.code);
+            assert_eq!(EffectKind::from_code(info.code, mins), Some(kind));
+            assert!(info.params.iter().all(|param| param.min <= param.max));
+            assert_eq!(info.code as usize, index + 1);
+        }
+    }
+
+    /// Tests that the list has exactly one entry per kind of effect.
+    #[test]
+    fn test_effects_cover_every_kind() {
+        let codes =
+            (1..=u8::MAX.into()).filter(|&code| EffectKind::from_code(code, [1; 3]).is_some());
+        assert_eq!(codes.clone().count(), EFFECTS.len());
+        for code in codes {
+            assert_eq!(EFFECTS.iter().filter(|info| info.code == code).count(), 1, "{}", code);
+        }
+    }
+
+    /// Tests that out-of-range parameters are rejected.
+    #[test]
+    fn test_effect_info_ranges() {
+        let heartbeat = &EFFECTS[1];
+        assert_eq!(
+            heartbeat.kind([2, 2, 60]).unwrap(),
+            EffectKind::Heartbeat { flash_beats: 2, grouped_as: 2, bpm: 60 }
+        );
+        assert!(heartbeat.kind([2, 2, 0]).is_err());
+        assert!(EFFECTS[0].kind([100, 0, 0]).is_err());
+        assert_eq!(EffectKind::from_code(0, [0; 3]), None);
+    }
+}
@@ -8,11 +8,65 @@
 //! It is designed to be platform-agnostic and works with any microcontroller
 //! that implements the embedded-hal traits.
 
+pub mod borrowed;
+pub mod cancel;
+pub mod config;
+pub mod delay;
+pub mod duty;
+pub mod easing;
+pub mod gamma;
+pub mod group;
+pub mod heartbeat;
+pub mod kind;
+pub mod math;
+pub mod percent;
+pub mod ramp;
+pub mod rgb;
+pub mod sequence;
+pub mod soft;
+pub mod source;
+pub mod table;
+
+pub use borrowed::PinRef;
+pub use cancel::Cancel;
+use cancel::Watched;
+pub use config::Config;
+pub use delay::{BusyWait, Delay};
+pub use duty::DutyValue;
+pub use easing::Easing;
+pub use group::Group;
+pub use kind::{EffectInfo, EffectKind, EFFECTS};
+pub use percent::Percent;
+pub use ramp::Ramp;
+pub use rgb::RgbEffect;
+pub use sequence::{EffectSequence, EffectStep};
+pub use soft::SoftPwm;
+pub use source::BrightnessSource;
+
+/// Build a gamma lookup table at compile time
+///
+/// Expands to a `[u16; size]` constant sampling `x^gamma` like
+/// `gamma::GAMMA_2_2` (see `gamma::table`), ready for
+/// `RgbEffect::set_gamma_tables` when `size` is `256`:
+///
+/// ```ignore
+/// static WARM: [u16; 256] = led_effects::gamma_table!(gamma = 2.6, size = 256);
+/// led.set_gamma_tables(&WARM, &GAMMA_2_2, &WARM);
+/// ```
+#[macro_export]
+macro_rules! gamma_table {
+    (gamma = $gamma:expr, size = $size:expr $(,)?) => {
+        const { $crate::gamma::table::<{ $size }>($gamma) }
+    };
+}
 
 use core::marker::PhantomData;
 // Исправляем импорт для embedded-hal 0.2.7
+use embedded_hal::digital::v2::OutputPin;
 use embedded_hal::PwmPin;
-use cortex_m::asm;
+use embedded_hal::blocking::delay::DelayMs;
+use cortex_m::peripheral::{syst::SystClkSource, SYST};
+use rand_core::RngCore;
 
 #[cfg(feature = "defmt")]
 use defmt::Format;
@@ -21,277 +75,5750 @@ use defmt::Format;
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(Format))]
 pub enum Error {
-    /// PWM-related error
+    /// PWM-related error, e.g. a verified duty write did not take effect or
+    /// a pin reports a maximum duty of `0`
     Pwm,
     /// Invalid parameter error
     InvalidParameter,
 }
 
+impl Error {
+    /// A stable, non-zero numeric code for the error
+    ///
+    /// `Pwm` is `1` and `InvalidParameter` is `2`; the numbers never change
+    /// between releases. Small enough for a one-byte log entry, and can be
+    /// passed straight to `fault_code` to blink the error on the LED.
+    pub const fn code(&self) -> u8 {
+        match self {
+            Error::Pwm => 1,
+            Error::InvalidParameter => 2,
+        }
+    }
+}
+
+/// What an effect leaves on the LED once it finishes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub enum EndState {
+    /// Write the "off" duty, `0` unless changed with `set_off_duty`; the
+    /// historical behaviour
+    #[default]
+    Off,
+    /// Write `pwm_min`, leaving a dim glow
+    Min,
+    /// Leave the last animated value in place
+    Hold,
+}
+
+/// How eagerly the smooth effects update the LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub enum PowerMode {
+    /// Step as finely as the duty range, `set_fps` or `set_dither` allow
+    #[default]
+    Normal,
+    /// Render the smooth effects at a low, fixed frame rate to save power
+    LowUpdate,
+}
+
+/// Frame rate of the smooth effects in `PowerMode::LowUpdate`
+const LOW_UPDATE_FPS: u16 = 25;
+
+/// Length of a single time slice of the sparkle effect, in milliseconds
+const SPARKLE_SLICE_MS: u32 = 20;
+
+/// One Morse time unit of the `distress` signal (a dot), in milliseconds
+const DISTRESS_UNIT_MS: u32 = 200;
+
+/// Lit time of each symbol of the SOS pattern, in milliseconds
+const SOS_MS: [u32; 9] = [
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    3 * DISTRESS_UNIT_MS,
+    3 * DISTRESS_UNIT_MS,
+    3 * DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+];
+
+/// Dark time after each symbol of the SOS pattern, in milliseconds
+///
+/// One unit between symbols, three between letters and seven before the
+/// pattern repeats.
+const SOS_GAP_MS: [u32; 9] = [
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    3 * DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    3 * DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    DISTRESS_UNIT_MS,
+    7 * DISTRESS_UNIT_MS,
+];
+
+/// Nominal length of one `organic_breath` cycle, in milliseconds
+const ORGANIC_PERIOD_MS: u32 = 4_000;
+
+/// Time between two duty updates of `organic_breath`, in milliseconds
+const ORGANIC_FRAME_MS: u32 = 20;
+
+/// Length of one shallow breath of `layered_breath`, in milliseconds
+const LAYERED_SMALL_MS: u32 = 1_500;
+
+/// Length of one full-range breath of `layered_breath`, in milliseconds
+const LAYERED_BIG_MS: u32 = 4_000;
+
+/// Time a `fault_code` blink stays lit, in milliseconds
+const FAULT_ON_MS: u32 = 200;
+
+/// Time between two `fault_code` blinks of one group, in milliseconds
+const FAULT_OFF_MS: u32 = 300;
+
+/// Pause after each `fault_code` group, in milliseconds
+const FAULT_PAUSE_MS: u32 = 1_500;
+
+/// Brightness level of an `EffectKind` that stands for `pwm_max`
+const LEVEL_MAX: u32 = u16::MAX as u32;
+
+/// Core clock frequency assumed by the busy-wait delay until told otherwise
+///
+/// 48 MHz unless a `clk-72mhz`, `clk-125mhz` or `clk-168mhz` feature selects
+/// the board's clock at build time; if several are enabled (e.g. by
+/// `--all-features`), the highest one wins. `set_clock_hz` still overrides
+/// it at runtime.
+pub const DEFAULT_CLOCK_HZ: u32 = if cfg!(feature = "clk-168mhz") {
+    168_000_000
+} else if cfg!(feature = "clk-125mhz") {
+    125_000_000
+} else if cfg!(feature = "clk-72mhz") {
+    72_000_000
+} else {
+    48_000_000
+};
+
+/// Lowest clock frequency that still has a whole number of cycles per millisecond
+const MIN_CLOCK_HZ: u32 = 1_000;
+
+/// Length of the reference delay used by `LEDEffect::calibrate`, in milliseconds
+const CALIBRATION_MS: u32 = 10;
+
+/// Attack/hold/release envelope played by `LEDEffect::play_envelope`
+///
+/// `D` is the duty type of the PWM pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<D> {
+    /// Time to ramp from `pwm_min` up to `peak`, in milliseconds
+    pub attack_ms: u32,
+    /// Time to stay at `peak`, in milliseconds
+    pub hold_ms: u32,
+    /// Time to ramp from `peak` back down to `pwm_min`, in milliseconds
+    pub release_ms: u32,
+    /// Highest duty reached; clamped into `[pwm_min, pwm_max]`
+    pub peak: D,
+}
+
+/// Snapshot of a non-blocking effect, returned by `LEDEffect::status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub struct EffectStatus {
+    /// The effect begun with `begin_frames`, if one is running
+    pub kind: Option<EffectKind>,
+    /// How far the running effect has got, `0..=100`; `100` when idle
+    pub progress: u8,
+    /// The current brightness as a percentage of `[pwm_min, pwm_max]`
+    pub brightness: u8,
+}
+
+/// Outcome of one `LEDEffect::render_slice`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub enum RenderProgress {
+    /// The effect has time left; call `render_slice` again
+    Pending,
+    /// The effect has finished, or none was begun
+    Done,
+}
+
+/// An effect being driven one step at a time by `LEDEffect::advance`
+#[derive(Debug, Clone, Copy)]
+enum Stepper {
+    /// Triangle from `pwm_min` up to `pwm_max` and back over `total` steps
+    Breath { step: u32, total: u32 },
+    /// Linear ramp from `from` to `to` over `total` steps
+    Fade { from: u32, to: u32, step: u32, total: u32 },
+}
+
+/// Offset above `pwm_min` of a breathing cycle at `position`, in 1/256 duty steps.
+///
+/// A cycle is `3 * third` steps long: it rises over the first third, peaks
+/// at exactly `range` at the start of the second, falls over the second
+/// third and stays at `0` for the last.
+///
+/// # Arguments
+///
+/// * `range` - The width of the duty range (`pwm_max - pwm_min`).
+/// * `position` - The step within the cycle, in `0..3 * third`.
+/// * `third` - The number of steps in each third of the cycle.
+fn breath_offset(range: u32, position: u32, third: u32) -> u64 {
+    let rise = Ramp::new(0, range, third);
+    if position < third {
+        rise.fine_at(position)
+    } else if position < 2 * third {
+        rise.fine_at(2 * third - position)
+    } else {
+        0
+    }
+}
+
+/// Convert whole seconds into milliseconds, saturating at `u32::MAX`.
+///
+/// # Arguments
+///
+/// * `secs` - The duration in seconds.
+fn secs_to_ms(secs: u32) -> u32 {
+    secs.saturating_mul(1_000)
+}
+
+/// Advance a xorshift32 generator and return its new state.
+///
+/// # Arguments
+///
+/// * `state` - The current, non-zero state of the generator.
+fn xorshift32(mut state: u32) -> u32 {
+    state ^= state << 13;
+    state ^= state >> 17;
+    state ^= state << 5;
+    state
+}
+
+/// Interpolate linearly from `from` to `to`, `at` of the way through `over`.
+///
+/// # Arguments
+///
+/// * `from` - The value at the start.
+/// * `to` - The value at the end, returned once `at` reaches `over`.
+/// * `at` - The position, in the same unit as `over`.
+/// * `over` - The length of the interpolation.
+fn lerp(from: u32, to: u32, at: u64, over: u64) -> u32 {
+    if at >= over {
+        return to;
+    }
+    let distance = (u64::from(from.abs_diff(to)) * at / over) as u32;
+    if to >= from {
+        from + distance
+    } else {
+        from - distance
+    }
+}
+
+/// Period and amplitude of one `organic_breath` cycle.
+///
+/// # Arguments
+///
+/// * `roll` - A fresh random number.
+/// * `range` - The distance between `pwm_min` and `pwm_max`.
+fn organic_cycle(roll: u32, range: u64) -> (u32, u64) {
+    let spread = ORGANIC_PERIOD_MS / 4;
+    let period = ORGANIC_PERIOD_MS - spread / 2 + (roll & 0xFFFF) % (spread + 1);
+    let amplitude = range - range * u64::from((roll >> 16) & 0xFF) / 1024;
+    (period, amplitude)
+}
+
+/// Height above `pwm_min` of an `organic_breath` cycle `t` ms in.
+///
+/// # Arguments
+///
+/// * `amplitude` - The height of the cycle's peak.
+/// * `t` - The time since the cycle started, in milliseconds.
+/// * `period` - The length of the cycle, in milliseconds.
+fn organic_offset(amplitude: u64, t: u32, period: u32) -> u32 {
+    let angle = ((u64::from(t) << 16) / u64::from(period)) as u16;
+    let cos = i64::from(math::cos_fixed(angle));
+    (amplitude * (32767 - cos) as u64 / 65534) as u32
+}
+
+/// Random number generator for the seeded effects of an `EffectKind`.
+struct Seeded(u32);
+
+impl Seeded {
+    /// Start a generator from `seed`; xorshift cannot leave `0`, so it acts as `1`.
+    fn new(seed: u32) -> Self {
+        Seeded(seed.max(1))
+    }
+}
+
+impl RngCore for Seeded {
+    fn next_u32(&mut self) -> u32 {
+        self.0 = xorshift32(self.0);
+        self.0
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand_core::impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
 /// Main structure for LED effects
-pub struct LEDEffect<PWM>
+///
+/// `DELAY` supplies the waits between steps: `BusyWait` unless the effect
+/// was created with `with_delay` (see the `delay` module).
+pub struct LEDEffect<'a, PWM, DELAY = BusyWait>
 where
     PWM: PwmPin,
 {
     pin: PWM,
+    delay: DELAY,
     pwm_min: PWM::Duty,
     pwm_max: PWM::Duty,
     pwm_mid: PWM::Duty,
+    safe_max: PWM::Duty,
+    off_duty: PWM::Duty,
+    min_on: PWM::Duty,
+    duty: PWM::Duty,
+    master: Percent,
+    phase: u16,
+    follow_slew: u32,
+    deadband: u16,
+    last_input: Option<u16>,
+    cycle_crossfade_ms: u32,
+    trough_hold_ms: u32,
+    clock_hz: u32,
+    watchdog: Option<(&'a mut (dyn FnMut() + Send), u32)>,
+    since_kick_ms: u32,
+    yield_hook: Option<&'a mut (dyn FnMut(u32) + Send)>,
+    settle_hook: Option<&'a mut (dyn FnMut(u8) + Send)>,
+    soft_drive: Option<fn(&mut PWM, u32, u32)>,
+    jitter: Option<(u32, u32)>,
+    speed: u16,
+    speed_carry: u32,
+    end_state: EndState,
+    power_mode: PowerMode,
+    budget_ms: Option<u32>,
+    cancel: Option<Watched>,
+    stepper: Option<Stepper>,
+    frames: Option<(EffectKind, u32, u32)>,
+    inverted: bool,
+    dither: bool,
+    dither_error: u8,
+    fps: Option<u16>,
+    gamma: bool,
+    default_effect: Option<EffectKind>,
+    asleep: bool,
+    verify: bool,
+    fault: bool,
+    min_update_ms: u32,
+    since_update_ms: u32,
+    pending: Option<PWM::Duty>,
+    jnd: u8,
+    held: Option<PWM::Duty>,
+    max_slew: u32,
+    auto_disable: bool,
+    disabled: bool,
     _phantom: PhantomData<PWM>,
 }
 
-impl<PWM> LEDEffect<PWM>
+impl<'a, PWM> LEDEffect<'a, PWM>
 where
     PWM: PwmPin,
-    PWM::Duty: Into<u32> + From<u32> + Copy + Ord,
+    PWM::Duty: DutyValue,
 {
     /// Create a new LEDEffect instance
-    pub fn new(mut pin: PWM, pwm_min: PWM::Duty, pwm_max: PWM::Duty) -> Result<Self, Error> {
-        if pwm_max <= pwm_min {
+    ///
+    /// The parameters are checked with `validate` against the default clock.
+    pub fn new(pin: PWM, pwm_min: PWM::Duty, pwm_max: PWM::Duty) -> Result<Self, Error> {
+        Self::with_delay(pin, pwm_min, pwm_max, BusyWait)
+    }
+
+    /// Create an `LEDEffect` spanning the pin's own duty range
+    ///
+    /// The zero-configuration entry point: `pwm_max` is the pin's
+    /// `get_max_duty()` and `pwm_min` is 1% of it, at least `1`, so the
+    /// dimmest level of the effects still glows. Returns `Error::Pwm` for
+    /// a pin whose maximum duty is `0`, which points at a misconfigured
+    /// timer, and `Error::InvalidParameter` for pins whose maximum duty is
+    /// too small to leave a range (below `3`).
+    pub fn auto(pin: PWM) -> Result<Self, Error> {
+        let pwm_max = pin.get_max_duty();
+        if Self::to_u32(pwm_max) == 0 {
+            return Err(Error::Pwm);
+        }
+        let pwm_min = (Self::to_u32(pwm_max) / 100).max(1);
+        Self::new(pin, Self::from_u32(pwm_min), pwm_max)
+    }
+
+    /// Rebuild an `LEDEffect` from a stored `Config`
+    ///
+    /// Applies the range, clock, gamma, inversion and default effect of
+    /// `config`. Returns `Error::InvalidParameter` if the configuration fails
+    /// `validate`, names an unknown effect, or has a range that does not fit
+    /// the pin's duty type.
+    pub fn from_config(pin: PWM, config: &Config) -> Result<Self, Error> {
+        Self::validate(config.pwm_min, config.pwm_max, config.clock_hz)?;
+        let default_effect = config.default_effect()?;
+
+        let pwm_min = Self::from_u32(config.pwm_min);
+        let pwm_max = Self::from_u32(config.pwm_max);
+        if Self::to_u32(pwm_max) != config.pwm_max {
             return Err(Error::InvalidParameter);
         }
 
-        let pwm_mid = From::from(
-            pwm_min.into() + (pwm_max.into() - pwm_min.into()) / 2
+        let mut led = Self::new(pin, pwm_min, pwm_max)?;
+        led.set_clock_hz(config.clock_hz)?;
+        led.gamma = config.gamma != 0;
+        led.inverted = config.inverted != 0;
+        led.default_effect = default_effect;
+        Ok(led)
+    }
+}
+
+impl<'a, PWM, DELAY> LEDEffect<'a, PWM, DELAY>
+where
+    PWM: PwmPin,
+    PWM::Duty: DutyValue,
+    DELAY: Delay,
+{
+    /// Create a new LEDEffect instance that waits with `delay`
+    ///
+    /// Like `new`, but the waits between steps go through `delay`, e.g. a
+    /// timer-based `DelayMs<u32>` from the HAL, instead of busy-waiting on
+    /// the core clock. The parameters are checked with `validate` against
+    /// the default clock.
+    pub fn with_delay(
+        mut pin: PWM,
+        pwm_min: PWM::Duty,
+        pwm_max: PWM::Duty,
+        delay: DELAY,
+    ) -> Result<Self, Error> {
+        Self::validate(Self::to_u32(pwm_min), Self::to_u32(pwm_max), DEFAULT_CLOCK_HZ)?;
+
+        let pwm_mid = Self::from_u32(
+            Self::to_u32(pwm_min) + (Self::to_u32(pwm_max) - Self::to_u32(pwm_min)) / 2
         );
 
         pin.enable();
+        let duty = pin.get_duty();
 
         Ok(Self {
             pin,
+            delay,
             pwm_min,
             pwm_max,
             pwm_mid,
+            safe_max: pwm_max,
+            off_duty: Self::from_u32(0),
+            min_on: Self::from_u32(0),
+            duty,
+            master: Percent::FULL,
+            phase: 0,
+            follow_slew: 0,
+            deadband: 0,
+            last_input: None,
+            cycle_crossfade_ms: 0,
+            trough_hold_ms: 0,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            watchdog: None,
+            since_kick_ms: 0,
+            yield_hook: None,
+            settle_hook: None,
+            soft_drive: None,
+            jitter: None,
+            speed: 100,
+            speed_carry: 0,
+            end_state: EndState::Off,
+            power_mode: PowerMode::Normal,
+            budget_ms: None,
+            cancel: None,
+            stepper: None,
+            frames: None,
+            inverted: false,
+            dither: false,
+            dither_error: 0,
+            fps: None,
+            gamma: true,
+            default_effect: None,
+            asleep: false,
+            verify: false,
+            fault: false,
+            min_update_ms: 0,
+            since_update_ms: u32::MAX,
+            pending: None,
+            jnd: 0,
+            held: None,
+            max_slew: 0,
+            auto_disable: false,
+            disabled: false,
             _phantom: PhantomData,
         })
     }
 
+    /// Capture the current setup as a `Config` for storage
+    pub fn to_config(&self) -> Config {
+        let mut config = Config {
+            pwm_min: Self::to_u32(self.pwm_min),
+            pwm_max: Self::to_u32(self.pwm_max),
+            clock_hz: self.clock_hz,
+            effect: config::EFFECT_NONE,
+            params: [0; 3],
+            gamma: self.gamma.into(),
+            inverted: self.inverted.into(),
+            reserved: [0; 2],
+        };
+        config.set_default_effect(self.default_effect);
+        config
+    }
+
+    /// Check a candidate configuration without touching any hardware
+    ///
+    /// Applies the same rules as `new` and `set_clock_hz`, so a configuration
+    /// read from user input or flash can be rejected before the PWM pin is
+    /// consumed:
+    ///
+    /// * `min` must be below `max`, with room for a distinct midpoint
+    ///   (`max - min >= 2`), which the heartbeat relies on;
+    /// * `clock_hz` must be at least 1 kHz so a millisecond is a whole
+    ///   number of cycles.
+    ///
+    /// Returns `Error::InvalidParameter` if any rule is broken.
+    pub fn validate(min: u32, max: u32, clock_hz: u32) -> Result<(), Error> {
+        if max <= min || max - min < 2 || clock_hz < MIN_CLOCK_HZ {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(())
+    }
+
     /// Create heartbeat effect
+    ///
+    /// Flashes `flash_beats` beats at `bpm` beats per minute. Beats come in
+    /// groups of `grouped_as` with a longer pause after each group: `2`
+    /// gives "ba-dum ... ba-dum", `1` a steady beat and `0` no pauses at all
+    /// (see the `heartbeat` module for the exact gaps).
+    ///
+    /// Returns `Error::InvalidParameter` if `bpm` is zero.
     pub fn heartbeat(
         &mut self,
         flash_beats: u32,
         grouped_as: u32,
         bpm: u32
     ) -> Result<(), Error> {
-        let period_time = (60_000 / bpm) / 6;
-        let short_period_time = period_time / 3;
-        let down_delay_time = (period_time * 2) / (self.pwm_mid.into() - self.pwm_min.into());
+        let (period_time, short_period_time, decay_time) =
+            self.heartbeat_timing(bpm).ok_or(Error::InvalidParameter)?;
+        let decay_steps = self.heartbeat_decay_steps(decay_time);
 
-        for n in 1..=flash_beats {
-            self.pin.set_duty(self.pwm_max);
+        'beats: for n in 1..=flash_beats {
+            self.write_duty(Self::to_u32(self.pwm_max));
             self.delay_ms(short_period_time);
 
-            self.pin.set_duty(self.pwm_min);
+            self.write_duty(Self::to_u32(self.pwm_min));
             self.delay_ms(short_period_time * 2);
 
-            self.pin.set_duty(self.pwm_mid);
+            self.write_duty(Self::to_u32(self.pwm_mid));
 
-            let mut current = self.pwm_mid;
-            while current >= self.pwm_min {
-                self.pin.set_duty(current);
-                self.delay_ms(down_delay_time);
-                current = From::from(current.into().saturating_sub(1));
+            // Schedule each step against the decay's total length, so that
+            // per-step rounding does not add up.
+            let mut elapsed = 0;
+            for step in 0..decay_steps {
+                self.write_duty(self.heartbeat_decay_level(step, decay_steps));
+                let next = (u64::from(decay_time) * (step + 1) / decay_steps) as u32;
+                self.delay_ms(next - elapsed);
+                elapsed = next;
+                if self.stopped() {
+                    break 'beats;
+                }
             }
 
-            let wait = if n % grouped_as != 0 {
-                period_time
-            } else if grouped_as == 1 {
-                period_time * 2
-            } else {
-                (period_time * 2) + (grouped_as * period_time)
-            };
+            self.delay_ms(heartbeat::wait(n, grouped_as, period_time));
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
 
-            self.delay_ms(wait);
+    /// Predict how long `heartbeat` runs with the given parameters, in milliseconds
+    ///
+    /// Each beat is a flash, a pause, a decay from `pwm_mid` down to
+    /// `pwm_min` lasting a third of the beat, and a gap that is longer after
+    /// the last beat of a group. Because of integer rounding in the period
+    /// math the total is usually not a whole multiple of `60_000 / bpm`.
+    /// The speed set with `set_speed` is taken into account; the rounding
+    /// remainder carried over from earlier delays can add one millisecond.
+    /// Assumes no `run_for` budget cuts the effect short and no jitter is
+    /// set (see `set_jitter`). Saturates at `u32::MAX`, and returns `0` for
+    /// a `bpm` of zero, which `heartbeat` rejects.
+    pub fn heartbeat_duration_ms(&self, flash_beats: u32, grouped_as: u32, bpm: u32) -> u32 {
+        self.heartbeat_length_ms(flash_beats, grouped_as, bpm)
+            .map_or(0, |total| self.at_speed(total))
+    }
+
+    /// Create realistic two-bump ("lub-dub") heartbeat effect
+    ///
+    /// Each of the `beats` beats lasts `60_000 / bpm` ms, like a pulse
+    /// monitor. Its active part holds two pulses close together: a strong
+    /// "lub" that flashes up to `pwm_max` and a weaker "dub" peaking at 60%
+    /// of the range, each with a quick rise and a slower decay. The rest of
+    /// the beat stays at `pwm_min`, approximating an ECG-like envelope. The
+    /// configured `EndState` is applied after the last beat.
+    ///
+    /// Returns `Error::InvalidParameter` if `bpm` is zero.
+    pub fn heartbeat_realistic(&mut self, bpm: u32, beats: u32) -> Result<(), Error> {
+        if bpm == 0 {
+            return Err(Error::InvalidParameter);
         }
-        self.pin.set_duty(From::from(0u32));
-        Ok(())
+
+        let period = 60_000 / bpm;
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+        let dub_peak: u32 = Self::to_u32(self.duty_from_fraction(3, 5));
+
+        let rise = period / 20;
+        let lub_decay = period / 8;
+        let gap = period / 16;
+        let dub_decay = period / 6;
+        let rest = period - (2 * rise + lub_decay + gap + dub_decay);
+
+        for _ in 0..beats {
+            self.ramp(min, max, rise);
+            self.ramp(max, min, lub_decay);
+            self.delay_ms(gap);
+            self.ramp(min, dub_peak, rise);
+            self.ramp(dub_peak, min, dub_decay);
+            self.delay_ms(rest);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
     }
 
     /// Create breathing effect
+    ///
+    /// One cycle lasts `duration` ms and is split into equal thirds: rising
+    /// from `pwm_min` to `pwm_max`, falling back to `pwm_min`, and resting
+    /// dim. The cycle starts at the point selected with `set_phase`. A dwell
+    /// set with `set_trough_hold_ms` is added when the fall reaches `pwm_min`.
+    /// With `set_dither` the levels are updated every millisecond.
     pub fn breath(&mut self, duration: u32) -> Result<(), Error> {
-        let period_time = duration / 6;
-        let up_delay = (period_time * 2) / (self.pwm_max.into() - self.pwm_min.into());
-        let down_delay = (period_time * 2) / (self.pwm_max.into() - self.pwm_min.into());
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let range = Self::to_u32(self.pwm_max) - min;
+        let third = self.breath_third(duration);
+        let steps = 3 * third;
+        let shift = self.phase_shift(steps);
 
-        let mut current = self.pwm_min;
-        while current < self.pwm_max {
-            self.pin.set_duty(current);
-            self.delay_ms(up_delay);
-            current = From::from(current.into().saturating_add(1));
-        }
+        let mut elapsed = 0;
+        for step in 0..steps {
+            let position = ((u64::from(step) + u64::from(shift)) % u64::from(steps)) as u32;
+            self.write_fine((u64::from(min) << 8) + breath_offset(range, position, third));
+            if position == 2 * third {
+                self.delay_ms(self.trough_hold_ms);
+            }
 
-        current = self.pwm_max;
-        while current > self.pwm_min {
-            self.pin.set_duty(current);
-            self.delay_ms(down_delay);
-            current = From::from(current.into().saturating_sub(1));
+            let next = (u64::from(duration) * u64::from(step + 1) / u64::from(steps)) as u32;
+            self.delay_ms(next - elapsed);
+            elapsed = next;
+            if self.stopped() {
+                break;
+            }
         }
-
-        self.delay_ms(period_time * 2);
-        self.pin.set_duty(From::from(0u32));
-        Ok(())
+        self.finish()
     }
 
-    /// Destroy the LED effect instance and return the underlying pin
-    pub fn destroy(self) -> PWM {
-        self.pin
+    /// Create breathing effect with a cycle of `secs` seconds
+    ///
+    /// Shorthand for `breath(secs * 1000)`, saturating at `u32::MAX` ms.
+    pub fn breath_secs(&mut self, secs: u32) -> Result<(), Error> {
+        self.breath(secs_to_ms(secs))
     }
 
-    /// Delays execution for a specified number of milliseconds.
+    /// Predict how long `breath` runs with the given duration, in milliseconds
     ///
-    /// This function uses a busy-wait loop to delay execution for the given
-    /// number of milliseconds. The delay is achieved by converting the given
-    /// time into clock cycles and using the `asm::delay` function to wait
-    /// for the specified number of cycles.
-    ///
-    /// # Arguments
+    /// A cycle takes exactly `duration` ms however many duty steps the range
+    /// allows, plus the dwell set with `set_trough_hold_ms`, adjusted for the
+    /// speed set with `set_speed` (up to a millisecond of rounding). Assumes no
+    /// `run_for` budget cuts the effect short and no jitter is set.
+    pub fn breath_duration_ms(&self, duration: u32) -> u32 {
+        self.at_speed(u64::from(duration) + u64::from(self.trough_hold_ms))
+    }
+
+    /// Turn the LED fully on (`pwm_max`) without animating
     ///
-    /// * `ms` - The number of milliseconds to delay execution.
+    /// Only fails in verify mode (see `set_verify`).
+    pub fn on(&mut self) -> Result<(), Error> {
+        self.write_now(self.pwm_max);
+        self.complete()
+    }
+
+    /// Turn the LED off without animating
     ///
-    /// # Example
+    /// Writes the "off" duty (see `set_off_duty`), the same level the effects
+    /// finish with. Only fails in verify mode (see `set_verify`).
+    pub fn off(&mut self) -> Result<(), Error> {
+        self.write_now(self.off_duty);
+        self.complete()
+    }
+
+    /// Blink `times` times, on at `pwm_max` for `on_ms` and off for `off_ms`
     ///
-    /// ```
-    /// led_effect.delay_ms(500); // Delays for 500 milliseconds
-    /// ```
-    #[inline(always)]
-    fn delay_ms(&self, ms: u32) {
-        let cycles = ms * self.clock_cycles_per_ms();
-        asm::delay(cycles);
+    /// Each blink ends with its off time, so the whole effect takes
+    /// `times * (on_ms + off_ms)` ms. The "off" duty (see `set_off_duty`) is
+    /// used between the flashes, and the configured `EndState` is applied at
+    /// the end.
+    pub fn blink(&mut self, on_ms: u32, off_ms: u32, times: u32) -> Result<(), Error> {
+        for _ in 0..times {
+            self.write_now(self.pwm_max);
+            self.delay_ms(on_ms);
+            self.write_now(self.off_duty);
+            self.delay_ms(off_ms);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
     }
 
-    /// Calculate the number of clock cycles per millisecond.
+    /// Write `duty` straight to the pin for custom control
     ///
-    /// This function returns the number of clock cycles that occur in one millisecond
-    /// based on the system clock frequency. For example, for a system running at 48MHz,
-    /// it returns 48,000 cycles per millisecond. Adjust the returned value if the system
-    /// clock frequency changes.
+    /// The value bypasses the configured range, the master brightness, the
+    /// safe maximum and output inversion, but it is still checked against
+    /// the hardware: anything above `get_max_duty()` returns
+    /// `Error::InvalidParameter` and is not written. Verify mode (see
+    /// `set_verify`) applies as usual. Later fades start from the brightness
+    /// this value represents.
+    pub fn set_duty_raw(&mut self, duty: PWM::Duty) -> Result<(), Error> {
+        if duty > self.pin.get_max_duty() {
+            return Err(Error::InvalidParameter);
+        }
+
+        self.wake();
+        self.resume();
+        self.pending = None;
+        self.held = None;
+        self.since_update_ms = 0;
+        self.duty = self.physical(duty);
+        self.pin.set_duty(duty);
+        if self.verify && self.pin.get_duty() != duty {
+            self.fault = true;
+        }
+        self.complete()
+    }
+
+    /// Fade out over `fade_ms` and disable the PWM output to save power
     ///
-    /// # Returns
+    /// Ramps down to `pwm_min`, writes the "off" duty (see `set_off_duty`)
+    /// and then calls `disable` on the pin. Any later write, whether from an
+    /// effect or a direct setter, wakes the pin up again first, so a sleeping
+    /// LED is never driven silently; `wake` does the same without writing.
+    pub fn sleep(&mut self, fade_ms: u32) -> Result<(), Error> {
+        let from = Self::to_u32(self.clamp_duty(Self::to_u32(self.current_duty())));
+        self.ramp(from, Self::to_u32(self.pwm_min), fade_ms);
+        self.write_now(self.off_duty);
+        if !self.disabled {
+            self.pin.disable();
+        }
+        self.disabled = false;
+        self.asleep = true;
+        self.complete()
+    }
+
+    /// Re-enable a PWM output disabled by `sleep`
     ///
-    /// * `u32` - The number of clock cycles in one millisecond.
-    ///```
-    ///#[inline(always)]
-    ///fn clock_cycles_per_ms(&self) -> u32 {
-    ///    // This should be adjusted based on your system clock
-    ///    // For example, for a 48MHz system:
-    ///    48_000 // cycles per ms at 48MHz
-    ///}
-    /// ```
-    #[inline(always)]
-    fn clock_cycles_per_ms(&self) -> u32 {
-        // This should be adjusted based on your system clock
-        // For example, for a 48MHz system:
-        48_000 // cycles per ms at 48MHz
+    /// Does nothing if the pin is not asleep.
+    pub fn wake(&mut self) {
+        if self.asleep {
+            self.pin.enable();
+            self.asleep = false;
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Whether the PWM output is disabled by `sleep`
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
 
-    // Создаем мок для тестирования
-    struct MockPwm {
-        duty: u32,
+    /// Set a perceptual brightness level without animating
+    ///
+    /// `level` spans `0..=65535` and is interpreted perceptually: it is run
+    /// through the gamma 2.2 curve (see `gamma::correct`) and then mapped
+    /// onto `[pwm_min, pwm_max]`, so `0` writes `pwm_min`, `65535` writes
+    /// `pwm_max` and equal steps in `level` look like equal steps in
+    /// brightness. Honours `set_inverted`, and skips the gamma curve when it
+    /// is turned off with `set_gamma`.
+    ///
+    /// Only fails in verify mode (see `set_verify`).
+    pub fn set_level(&mut self, level: u16) -> Result<(), Error> {
+        let linear = if self.gamma { gamma::correct(level) } else { level };
+        let duty = self.duty_from_fraction(linear.into(), u16::MAX.into());
+        self.write_now(duty);
+        self.complete()
     }
 
-    impl MockPwm {
-        /// Creates a new instance of `MockPwm` with the default duty cycle.
-        ///
-        /// This function initializes a `MockPwm` instance with a duty cycle set to `0`.
-        ///
-        /// # Returns
-        ///
-        /// * `Self` - A new `MockPwm` instance with the duty cycle initialized to `0`.
-        ///
-        /// ```
-        ///fn new() -> Self {
-        ///    Self { duty: 0 }
-        ///}
-        /// ```
-        fn new() -> Self {
-            Self { duty: 0 }
+    /// Map the fraction `numerator / denominator` onto `[pwm_min, pwm_max]`
+    ///
+    /// This is the scaling used by every percentage- or level-based API.
+    /// The multiply happens before the divide in 64-bit arithmetic and the
+    /// result is rounded to the nearest duty, so neither very wide nor very
+    /// narrow ranges lose precision: `0` maps to `pwm_min` and a full
+    /// fraction maps exactly to `pwm_max`. Fractions above one are clamped to
+    /// `pwm_max`; a zero `denominator` yields `pwm_min`.
+    pub fn duty_from_fraction(&self, numerator: u32, denominator: u32) -> PWM::Duty {
+        if denominator == 0 {
+            return self.pwm_min;
         }
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let range = u64::from(Self::to_u32(self.pwm_max) - min);
+        let numerator = u64::from(numerator.min(denominator));
+        let denominator = u64::from(denominator);
+        let offset = (range * numerator + denominator / 2) / denominator;
+        self.clamp_duty(min + offset as u32)
     }
 
-    impl PwmPin for MockPwm {
-        type Duty = u32;
-
-        /// Disables the PWM output.
-        ///
-        /// This function disables the PWM output and stops updating the duty cycle.
-        /// After calling this function, the `duty` field is no longer updated.
-        /// ```
-        ///#[inline(always)]
-        ///fn disable(&mut self) {}
-        /// ```
-        fn disable(&mut self) {}
-        /// Enables the PWM output.
-        ///
-        /// This function enables the PWM output and starts updating the duty cycle
-        /// based on the value of the `duty` field.
-        ///
-        fn enable(&mut self) {}
-        /// Returns the current duty cycle of the PWM pin.
-        ///
-        /// This function retrieves the current duty cycle value of the PWM pin.
-        /// The duty cycle is a measure of the proportion of 'on' time to the
-        /// regular interval or 'period' of the PWM signal. A higher duty cycle
-        /// corresponds to a brighter LED when used in LED applications.
-        ///
-        /// # Returns
-        ///
-        /// * `Self::Duty` - The current duty cycle of the PWM pin.
-        ///
-        fn get_duty(&self) -> Self::Duty {
-            self.duty
-        }
-        /// Returns the maximum possible duty cycle value of the PWM pin.
-        ///
-        /// # Returns
-        ///
-        /// * `Self::Duty` - The maximum possible duty cycle value of the PWM pin.
-        ///
-        fn get_max_duty(&self) -> Self::Duty {
-            255
-        }
-        /// Sets the duty cycle of the PWM pin.
-        ///
-        /// This function sets the duty cycle of the PWM pin to the given value.
-        /// The duty cycle is a measure of the proportion of 'on' time to the
-        /// regular interval or 'period' of the PWM signal. A higher duty cycle
-        /// corresponds to a brighter LED when used in LED applications.
-        ///
-        /// # Arguments
-        ///
-        /// * `duty` - The new duty cycle value of the PWM pin.
-        ///
-        fn set_duty(&mut self, duty: Self::Duty) {
-            self.duty = duty;
+    /// Fade from the current duty cycle to `target` over `duration_ms`
+    ///
+    /// The fade starts at whatever duty the pin currently reports and moves
+    /// linearly towards `target`, taking at most one step per millisecond.
+    /// When the fade completes the LED is left at `target`: unlike `breath`
+    /// and `heartbeat` there is no final "off" write, so `fade` doubles as a
+    /// ramp-and-hold primitive (e.g. brightening a backlight and keeping it lit).
+    ///
+    /// Returns `Error::InvalidParameter` if `target` lies outside
+    /// `[pwm_min, pwm_max]`.
+    pub fn fade(&mut self, target: PWM::Duty, duration_ms: u32) -> Result<(), Error> {
+        if target < self.pwm_min || target > self.pwm_max {
+            return Err(Error::InvalidParameter);
         }
+
+        self.ramp(Self::to_u32(self.current_duty()), Self::to_u32(target), duration_ms);
+        self.flush();
+        self.complete()
     }
 
-    /// Tests creating a new instance of the `LEDEffect` struct.
+    /// Fade to `target` over `secs` seconds and hold it
     ///
-    /// This test creates a new instance of the `LEDEffect` struct with a valid
-    /// set of parameters. The test asserts that the `LEDEffect` instance is created
-    /// successfully.
-    #[test]
-    fn test_new_led_effect() {
-        let pin = MockPwm::new();
-        let led = LEDEffect::new(pin, 5, 255);
-        assert!(led.is_ok());
+    /// Shorthand for `fade(target, secs * 1000)`, saturating at `u32::MAX` ms.
+    pub fn fade_secs(&mut self, target: PWM::Duty, secs: u32) -> Result<(), Error> {
+        self.fade(target, secs_to_ms(secs))
     }
 
-    /// Tests that creating a new `LEDEffect` instance with invalid parameters fails.
+    /// Fade to the level another LED is showing and hold it there
     ///
-    /// This test creates a new instance of the `LEDEffect` struct with an invalid
-    /// set of parameters (i.e. `pwm_min` >= `pwm_max`). The test asserts that the
-    /// `LEDEffect` instance cannot be created and that the error variant is
-    /// `Error::InvalidParameter`.
+    /// The building block for adding an LED to an animation that is
+    /// already running without a visible pop. Moves linearly from the
+    /// current duty (clamped into `[pwm_min, pwm_max]`, so a dark LED
+    /// starts from `pwm_min`) to `target_duty`, taking at most one step per
+    /// millisecond, and holds it. Unlike `fade`, a `target_duty` outside
+    /// the range is clamped rather than rejected, since the other LED may
+    /// be in a dark phase or configured with a different range.
     ///
+    /// To then continue in lockstep with a breath driven by `advance`,
+    /// copy its position with `breath_phase` and begin the same cycle:
+    ///
+    /// ```ignore
+    /// follower.sync_to(leader.current_duty(), 300)?;
+    /// follower.set_phase(leader.breath_phase().unwrap());
+    /// follower.begin_breath(TOTAL_STEPS)?;
+    /// // from here on, advance both from the same timer tick
+    /// ```
+    ///
+    /// The leader keeps moving while the follower fades in, so keep
+    /// `duration_ms` short compared to the cycle, or advance the leader
+    /// from an interrupt and sync to where it will be.
+    pub fn sync_to(&mut self, target_duty: PWM::Duty, duration_ms: u32) -> Result<(), Error> {
+        let from = self.clamp_duty(Self::to_u32(self.current_duty()));
+        let to = self.clamp_duty(Self::to_u32(target_duty));
+        self.ramp(Self::to_u32(from), Self::to_u32(to), duration_ms);
+        self.flush();
+        self.complete()
+    }
+
+    /// Fade to `target` along an easing curve and hold it
+    ///
+    /// Like `fade`, but the progress follows `easing`, updating the duty
+    /// once per millisecond (or once per frame, see `set_fps`). Curves such as `Easing::EaseOutBack` move past
+    /// the target before settling on it; those intermediate levels are
+    /// clamped into `[pwm_min, pwm_max]`, so a fade to `pwm_max` simply
+    /// flattens the overshoot.
+    ///
+    /// Returns `Error::InvalidParameter` if `target` lies outside
+    /// `[pwm_min, pwm_max]`.
+    pub fn fade_eased(
+        &mut self,
+        target: PWM::Duty,
+        duration_ms: u32,
+        easing: Easing,
+    ) -> Result<(), Error> {
+        if target < self.pwm_min || target > self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+
+        let from = i64::from(Self::to_u32(self.clamp_duty(Self::to_u32(self.current_duty()))));
+        let to = i64::from(Self::to_u32(target));
+        let steps = self.frames_in(duration_ms).unwrap_or(duration_ms.max(1));
+
+        let mut elapsed = 0;
+        for step in 1..=steps {
+            let t = (u64::from(step) << 16) / u64::from(steps);
+            let progress = i64::from(easing.apply(t as u32));
+            let value = from + (((to - from) * progress) >> 16);
+            self.write_duty(value.clamp(0, i64::from(u32::MAX)) as u32);
+
+            let next = (u64::from(duration_ms) * u64::from(step) / u64::from(steps)) as u32;
+            self.delay_ms(next - elapsed);
+            elapsed = next;
+            if self.stopped() {
+                break;
+            }
+        }
+        self.flush();
+        self.complete()
+    }
+
+    /// Fade to `target` along a custom curve and hold it
+    ///
+    /// `curve` maps normalized time (`0` at the start, `65535` at the end)
+    /// to normalized progress (`0` = the starting duty, `65535` = `target`),
+    /// so any shape can be used without a new `Easing` variant:
+    ///
+    /// ```ignore
+    /// // smoothstep S-curve
+    /// led.fade_fn(800, 2_000, |t| {
+    ///     let t = u64::from(t);
+    ///     ((3 * t * t * 65535 - 2 * t * t * t) / (65535 * 65535)) as u16
+    /// })?;
+    /// ```
+    ///
+    /// The duty is updated once per millisecond (or once per frame, see
+    /// `set_fps`) and ends on `curve(65535)`, which is `target` for a curve
+    /// that finishes at `65535`. Whatever the curve returns, the duty
+    /// written stays within `[pwm_min, pwm_max]`.
+    ///
+    /// Returns `Error::InvalidParameter` if `target` lies outside
+    /// `[pwm_min, pwm_max]`.
+    pub fn fade_fn(
+        &mut self,
+        target: PWM::Duty,
+        duration_ms: u32,
+        curve: impl Fn(u16) -> u16,
+    ) -> Result<(), Error> {
+        if target < self.pwm_min || target > self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+
+        let from = i64::from(Self::to_u32(self.clamp_duty(Self::to_u32(self.current_duty()))));
+        let to = i64::from(Self::to_u32(target));
+        let steps = self.frames_in(duration_ms).unwrap_or(duration_ms.max(1));
+
+        let mut elapsed = 0;
+        for step in 1..=steps {
+            let t = u64::from(step) * u64::from(u16::MAX) / u64::from(steps);
+            let progress = i64::from(curve(t as u16));
+            let value = from + (to - from) * progress / i64::from(u16::MAX);
+            self.write_duty(value.clamp(0, i64::from(u32::MAX)) as u32);
+
+            let next = (u64::from(duration_ms) * u64::from(step) / u64::from(steps)) as u32;
+            self.delay_ms(next - elapsed);
+            elapsed = next;
+            if self.stopped() {
+                break;
+            }
+        }
+        self.flush();
+        self.complete()
+    }
+
+    /// Turn on gently from "off" to a steady `hold_level` and leave it lit
+    ///
+    /// Writes the "off" duty (see `set_off_duty`), then ramps linearly from
+    /// `pwm_min` up to `hold_level` over `rise_ms` and returns with the LED
+    /// holding that level, ignoring the configured `EndState`. Meant for
+    /// always-on ambient lights such as a nightlight: unlike `fade`, it
+    /// always starts from dark, whatever was shown before.
+    ///
+    /// Returns `Error::InvalidParameter` if `hold_level` lies outside
+    /// `[pwm_min, pwm_max]`.
+    pub fn breathe_in_and_hold(
+        &mut self,
+        rise_ms: u32,
+        hold_level: PWM::Duty,
+    ) -> Result<(), Error> {
+        if hold_level < self.pwm_min || hold_level > self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+
+        self.write_now(self.off_duty);
+        self.ramp(Self::to_u32(self.pwm_min), Self::to_u32(hold_level), rise_ms);
+        self.flush();
+        self.complete()
+    }
+
+    /// Show the time left of a `total_ms` countdown as brightness
+    ///
+    /// Starts at `pwm_max` and dims towards `pwm_min` in proportion to the
+    /// time remaining. When the time is up the "off" duty (see
+    /// `set_off_duty`) is written, regardless of the configured `EndState`.
+    /// The remaining fraction is treated as a perceptual level, so with
+    /// gamma correction on (see `set_gamma`) the LED looks half as bright
+    /// at the halfway point rather than racing through the dark end. The
+    /// duty is updated every millisecond, or once per frame with `set_fps`.
+    pub fn countdown(&mut self, total_ms: u32) -> Result<(), Error> {
+        let steps = self.frames_in(total_ms).unwrap_or(total_ms.max(1));
+
+        let mut elapsed = 0;
+        for step in 0..steps {
+            let remaining = u64::from(steps - step) * u64::from(u16::MAX) / u64::from(steps);
+            let level = remaining as u16;
+            let linear = if self.gamma { gamma::correct(level) } else { level };
+            let duty = self.duty_from_fraction(linear.into(), u16::MAX.into());
+            self.write_duty(Self::to_u32(duty));
+
+            let next = (u64::from(total_ms) * u64::from(step + 1) / u64::from(steps)) as u32;
+            self.delay_ms(next - elapsed);
+            elapsed = next;
+            if self.stopped() {
+                break;
+            }
+        }
+        self.write_now(self.off_duty);
+        self.complete()
+    }
+
+    /// Run `countdown` until it completes or `cancel` is raised
+    ///
+    /// A cancelled countdown turns off as if its time had run out.
+    pub fn countdown_cancellable(
+        &mut self,
+        total_ms: u32,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        self.cancellable(cancel, |led| led.countdown(total_ms))
+    }
+
+    /// Fade between two explicit levels regardless of the current duty
+    ///
+    /// Snaps to `from`, then moves linearly to `to` over `duration_ms` and
+    /// holds it, like `fade`. Both endpoints are clamped into
+    /// `[pwm_min, pwm_max]` and either direction is allowed, which makes
+    /// sequences reproducible no matter where the LED was left.
+    pub fn fade_between(
+        &mut self,
+        from: PWM::Duty,
+        to: PWM::Duty,
+        duration_ms: u32,
+    ) -> Result<(), Error> {
+        let from: u32 = Self::to_u32(self.clamp_duty(Self::to_u32(from)));
+        let to: u32 = Self::to_u32(self.clamp_duty(Self::to_u32(to)));
+
+        self.write_duty(from);
+        self.ramp(from, to, duration_ms);
+        self.flush();
+        self.complete()
+    }
+
+    /// Smoothly move from whatever the LED shows now to where the next effect starts
+    ///
+    /// Fades from the current duty to `target_start` over `duration_ms` and
+    /// holds it, so switching modes does not pop. Unlike `fade`, the target
+    /// is clamped into `[pwm_min, pwm_max]` instead of rejected, and an LED
+    /// left off by the previous effect rises from `pwm_min`. In an
+    /// `EffectSequence` the same is achieved with an `EffectStep::Fade`
+    /// between two steps.
+    pub fn transition_to(
+        &mut self,
+        target_start: PWM::Duty,
+        duration_ms: u32,
+    ) -> Result<(), Error> {
+        let from = Self::to_u32(self.clamp_duty(Self::to_u32(self.current_duty())));
+        let to = Self::to_u32(self.clamp_duty(Self::to_u32(target_start)));
+
+        self.ramp(from, to, duration_ms);
+        self.flush();
+        self.complete()
+    }
+
+    /// Show progress as brightness, slewing from the current level
+    ///
+    /// `percent` is clamped to `100` and mapped linearly onto
+    /// `[pwm_min, pwm_max]`; the LED then fades there over `slew_ms` and
+    /// holds. Meant to be called repeatedly as progress updates arrive (e.g.
+    /// a charging indicator): each call continues from wherever the last one
+    /// left off, so increasing percentages produce a smooth rising glow.
+    /// Changes within the deadband set with `set_deadband` are ignored.
+    pub fn set_progress(&mut self, percent: u8, slew_ms: u32) -> Result<(), Error> {
+        let percent = Percent::new(percent).unwrap_or(Percent::FULL);
+        let input = self.debounced(percent.of(u32::from(u16::MAX)) as u16);
+        let target = self.duty_from_fraction(input.into(), u16::MAX.into());
+        self.transition_to(target, slew_ms)
+    }
+
+    /// Create cyclic brightness effect
+    ///
+    /// Each of the `cycles` periods ramps from `pwm_min` up to `pwm_max` and
+    /// then wraps back to `pwm_min` to start over, giving a "rotating" feel:
+    ///
+    /// * `breath` reverses direction at the top, so the dimming half looks
+    ///   like the brightening half played backwards.
+    /// * `cycle` with no crossfade (the default) drops from full brightness
+    ///   to `pwm_min` in a single hard edge, like a sawtooth.
+    /// * `cycle` with a crossfade (see `set_cycle_crossfade_ms`) spends that
+    ///   much of each period fading quickly back down, softening the wrap
+    ///   while still reading as a one-directional loop.
+    ///
+    /// `period_ms` includes the crossfade. The configured `EndState` is
+    /// applied after the last cycle.
+    pub fn cycle(&mut self, period_ms: u32, cycles: u32) -> Result<(), Error> {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+        let crossfade = self.cycle_crossfade_ms.min(period_ms);
+        let rise = period_ms - crossfade;
+
+        for n in 0..cycles {
+            if n == 0 || crossfade == 0 {
+                self.write_duty(min);
+            }
+            self.ramp(min, max, rise);
+            if crossfade > 0 && !self.stopped() {
+                self.ramp(max, min, crossfade);
+            }
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Create a layered breathing effect
+    ///
+    /// Plays `small_cycles` shallow breaths near the top of the range, each
+    /// a 1.5 s triangle between `small_low` and `pwm_max`, followed by
+    /// `big_cycles` full-range breaths of 4 s from `pwm_max` down to
+    /// `pwm_min` and back. The first breath rises from `pwm_min`; call it in
+    /// a loop for a continuous ambient effect. The configured `EndState` is
+    /// applied at the end.
+    ///
+    /// Returns `Error::InvalidParameter` unless `small_low` lies strictly
+    /// between `pwm_min` and `pwm_max`.
+    pub fn layered_breath(
+        &mut self,
+        small_cycles: u32,
+        small_low: PWM::Duty,
+        big_cycles: u32,
+    ) -> Result<(), Error> {
+        if small_low <= self.pwm_min || small_low >= self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+        let low: u32 = Self::to_u32(small_low);
+
+        let mut from = min;
+        self.write_duty(from);
+        let layers = [(small_cycles, low, LAYERED_SMALL_MS), (big_cycles, min, LAYERED_BIG_MS)];
+        'layers: for (cycles, bottom, period_ms) in layers {
+            for _ in 0..cycles {
+                self.ramp(from, max, period_ms / 2);
+                if self.stopped() {
+                    break 'layers;
+                }
+                self.ramp(max, bottom, period_ms - period_ms / 2);
+                if self.stopped() {
+                    break 'layers;
+                }
+                from = bottom;
+            }
+        }
+        self.finish()
+    }
+
+    /// Play an attack/hold/release envelope
+    ///
+    /// Ramps from `pwm_min` up to `env.peak` over `env.attack_ms`, holds the
+    /// peak for `env.hold_ms` and ramps back down to `pwm_min` over
+    /// `env.release_ms`, then applies the configured `EndState`. The peak is
+    /// clamped into `[pwm_min, pwm_max]`, and any phase may be zero-length
+    /// (a zero attack jumps straight to the peak).
+    pub fn play_envelope(&mut self, env: &Envelope<PWM::Duty>) -> Result<(), Error> {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let peak: u32 = Self::to_u32(self.clamp_duty(Self::to_u32(env.peak)));
+
+        self.ramp(min, peak, env.attack_ms);
+        if !self.stopped() {
+            self.delay_ms(env.hold_ms);
+        }
+        if !self.stopped() {
+            self.ramp(peak, min, env.release_ms);
+        }
+        self.finish()
+    }
+
+    /// Create throbbing effect
+    ///
+    /// Each of the `cycles` cycles brightens quickly from `pwm_min` to
+    /// `pwm_max` over `attack_ms` and then dims slowly back over `decay_ms`,
+    /// like a slow-beating light. Unlike `breath` the two halves need not be
+    /// symmetric: each is stepped independently, at most once per
+    /// millisecond and duty value. The configured `EndState` is applied
+    /// after the last cycle.
+    pub fn throb(&mut self, attack_ms: u32, decay_ms: u32, cycles: u32) -> Result<(), Error> {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+
+        for _ in 0..cycles {
+            self.ramp(min, max, attack_ms);
+            if self.stopped() {
+                break;
+            }
+            self.ramp(max, min, decay_ms);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Create organic breathing effect
+    ///
+    /// Breathes along a sine curve (dim → bright → dim) for `duration_ms`,
+    /// but every cycle draws a fresh period within ±12.5% of a nominal four
+    /// seconds and a fresh peak within the top quarter of the range. The
+    /// perturbations are small enough to stay recognisably a breath while
+    /// avoiding the mechanical look of `breath`. The last cycle is cut short
+    /// when `duration_ms` runs out, then the configured `EndState` applies.
+    pub fn organic_breath<R: RngCore>(&mut self, rng: &mut R, duration_ms: u32) -> Result<(), Error> {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let range = u64::from(Self::to_u32(self.pwm_max) - min);
+
+        let mut elapsed = 0;
+        while elapsed < duration_ms && !self.stopped() {
+            let (period, amplitude) = organic_cycle(rng.next_u32(), range);
+
+            let mut t = 0;
+            while t < period && elapsed < duration_ms {
+                self.write_duty(min + organic_offset(amplitude, t, period));
+
+                let frame = ORGANIC_FRAME_MS.min(period - t).min(duration_ms - elapsed);
+                self.delay_ms(frame);
+                t += frame;
+                elapsed += frame;
+                if self.stopped() {
+                    break;
+                }
+            }
+        }
+        self.finish()
+    }
+
+    /// Play back a pre-rendered brightness timeline
+    ///
+    /// Writes each sample of `table` in turn, `interval_ms` apart, then
+    /// applies the configured `EndState`. Samples span `0..=65535` and are
+    /// mapped linearly onto `[pwm_min, pwm_max]` (see `duty_from_fraction`),
+    /// so any curve shaping such as gamma must already be baked into the
+    /// table. `table::breath_table` renders a suitable breathing cycle at
+    /// compile time.
+    pub fn play_static(&mut self, table: &'static [u16], interval_ms: u32) -> Result<(), Error> {
+        for &sample in table {
+            let duty = self.duty_from_fraction(sample.into(), u16::MAX.into());
+            self.write_duty(Self::to_u32(duty));
+            self.delay_ms(interval_ms);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Render a `BrightnessSource` onto the LED for `duration_ms`
+    ///
+    /// Samples `source` every `interval_ms`, passing the time since the
+    /// start of the effect, and writes each level mapped linearly onto
+    /// `[pwm_min, pwm_max]` like `play_static`. The last sample is held for
+    /// whatever is left of `duration_ms`, then the configured `EndState` is
+    /// applied. See the `source` module for composing sources.
+    ///
+    /// Returns `Error::InvalidParameter` if `interval_ms` is zero.
+    pub fn play_source<S: BrightnessSource>(
+        &mut self,
+        source: &mut S,
+        duration_ms: u32,
+        interval_ms: u32,
+    ) -> Result<(), Error> {
+        if interval_ms == 0 {
+            return Err(Error::InvalidParameter);
+        }
+
+        let mut elapsed = 0;
+        while elapsed < duration_ms {
+            let level = source.level(elapsed);
+            let duty = self.duty_from_fraction(level.into(), u16::MAX.into());
+            self.write_duty(Self::to_u32(duty));
+            let wait = interval_ms.min(duration_ms - elapsed);
+            self.delay_ms(wait);
+            elapsed += wait;
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Create brightness staircase effect
+    ///
+    /// Steps through `levels` evenly spaced duty values from `pwm_min` to
+    /// `pwm_max` inclusive, holding each for `hold_ms`. Handy for bringing up
+    /// new hardware and checking the PWM response across the full range.
+    /// A single level holds `pwm_max`.
+    ///
+    /// Returns `Error::InvalidParameter` if `levels` is zero.
+    pub fn staircase(&mut self, levels: u32, hold_ms: u32) -> Result<(), Error> {
+        if levels == 0 {
+            return Err(Error::InvalidParameter);
+        }
+
+        let last = levels - 1;
+
+        for i in 0..levels {
+            let level = if last == 0 {
+                self.pwm_max
+            } else {
+                self.duty_from_fraction(i, last)
+            };
+            self.write_duty(Self::to_u32(level));
+            self.delay_ms(hold_ms);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Prepare a breathing cycle to be driven step by step with `advance`
+    ///
+    /// The cycle ramps from `pwm_min` to `pwm_max` and back to `pwm_min`
+    /// over `total_steps` calls to `advance`, starting at the point selected
+    /// with `set_phase`. Nothing is written until the first `advance`.
+    /// Replaces any effect previously begun.
+    ///
+    /// Returns `Error::InvalidParameter` if `total_steps` is zero.
+    pub fn begin_breath(&mut self, total_steps: u32) -> Result<(), Error> {
+        if total_steps == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        self.stepper = Some(Stepper::Breath {
+            step: 0,
+            total: total_steps,
+        });
+        Ok(())
+    }
+
+    /// Prepare a fade to `target` to be driven step by step with `advance`
+    ///
+    /// The fade starts from the current duty and reaches `target` on the
+    /// last of `total_steps` calls to `advance`, then holds it. Replaces any
+    /// effect previously begun.
+    ///
+    /// Returns `Error::InvalidParameter` if `total_steps` is zero or
+    /// `target` lies outside `[pwm_min, pwm_max]`.
+    pub fn begin_fade(&mut self, target: PWM::Duty, total_steps: u32) -> Result<(), Error> {
+        if total_steps == 0 || target < self.pwm_min || target > self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+        self.stepper = Some(Stepper::Fade {
+            from: Self::to_u32(self.current_duty()),
+            to: Self::to_u32(target),
+            step: 0,
+            total: total_steps,
+        });
+        Ok(())
+    }
+
+    /// Advance the effect prepared with `begin_breath`/`begin_fade` by one step
+    ///
+    /// Writes the duty for the next step without any delay, which makes it
+    /// suitable for calling from a periodic timer interrupt: the timer period
+    /// sets the speed of the effect. Returns `true` while the effect is still
+    /// in progress and `false` once it has completed (or when nothing was
+    /// begun). A breathing cycle applies the configured `EndState` after its
+    /// last step; a fade holds its target. In verify mode a failed write
+    /// ends the effect early. The limit set with `set_max_slew` is not
+    /// applied here, since its ramps would have to wait inside the call.
+    pub fn advance(&mut self) -> bool {
+        let max_slew = core::mem::take(&mut self.max_slew);
+        let running = self.step_stepper();
+        self.max_slew = max_slew;
+        running
+    }
+
+    /// Write the next step of the effect prepared with `begin_breath` or
+    /// `begin_fade`, for `advance`.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` while the effect is still in progress.
+    fn step_stepper(&mut self) -> bool {
+        let Some(stepper) = self.stepper else {
+            return false;
+        };
+
+        let (value, next) = match stepper {
+            Stepper::Breath { step, total } => {
+                let min: u32 = Self::to_u32(self.pwm_min);
+                let range = u64::from(Self::to_u32(self.pwm_max) - min);
+                let position = (u64::from(step) + u64::from(self.phase_shift(total))) % u64::from(total);
+                let peak = u64::from(total / 2);
+                let last = u64::from(total - 1);
+                let offset = if peak == 0 {
+                    range
+                } else if position <= peak {
+                    range * position / peak
+                } else {
+                    range * (last - position) / (last - peak)
+                };
+                let next = Stepper::Breath { step: step + 1, total };
+                (min + offset as u32, next)
+            }
+            Stepper::Fade { from, to, step, total } => {
+                let value = (Ramp::new(from, to, total).fine_at(step + 1) >> 8) as u32;
+                let next = Stepper::Fade { from, to, step: step + 1, total };
+                (value, next)
+            }
+        };
+        self.write_now(self.clamp_duty(value));
+
+        let (Stepper::Breath { step, total } | Stepper::Fade { step, total, .. }) = next;
+        if step < total && !self.fault {
+            self.stepper = Some(next);
+            return true;
+        }
+
+        self.stepper = None;
+        if matches!(next, Stepper::Breath { .. }) {
+            let _ = self.finish();
+        }
+        self.fault = false;
+        false
+    }
+
+    /// Where the breathing cycle begun with `begin_breath` stands
+    ///
+    /// Returns the position of the step the next `advance` writes, on the
+    /// same `0..=65535` scale as `set_phase`, or `None` if no breath is in
+    /// progress. Another LED set to this phase and begun with the same
+    /// `total_steps` then writes the same levels on each `advance`.
+    pub fn breath_phase(&self) -> Option<u16> {
+        let Some(Stepper::Breath { step, total }) = self.stepper else {
+            return None;
+        };
+        let position = (u64::from(step) + u64::from(self.phase_shift(total))) % u64::from(total);
+        let phase = (position << 16).div_ceil(u64::from(total));
+        Some(phase.min(u16::MAX.into()) as u16)
+    }
+
+    /// Prepare `effect` to be pulled frame by frame with `next_frame`
+    ///
+    /// Replaces any effect previously begun this way. The effect's length
+    /// is taken at the current speed (see `set_speed`); a `breath` cycle
+    /// lasts its nominal duration, without the trough dwell.
+    ///
+    /// Returns `Error::InvalidParameter` for parameters the effect itself
+    /// would reject (such as a zero `bpm` or `levels`), and for a
+    /// `FaultCode` repeated forever.
+    pub fn begin_frames(&mut self, effect: EffectKind) -> Result<(), Error> {
+        let length = self.kind_length_ms(effect).ok_or(Error::InvalidParameter)?;
+        let total = self.at_speed(length);
+        self.frames = Some((effect, 0, total));
+        Ok(())
+    }
+
+    /// Advance the effect prepared with `begin_frames` by `dt_ms` and return
+    /// the duty to show for this frame
+    ///
+    /// Nothing is written to the pin: the caller's render loop owns the
+    /// timing and the output, and writes the returned value itself. The
+    /// value already includes the master brightness, the safe maximum and
+    /// inversion, so it can go straight to `set_duty`. Call with a `dt_ms`
+    /// of `0` to get the very first frame. Returns `None` once the effect
+    /// has run its full length (or when nothing was begun).
+    pub fn next_frame(&mut self, dt_ms: u32) -> Option<PWM::Duty> {
+        let (effect, elapsed, total) = self.frames?;
+        let elapsed = elapsed.saturating_add(dt_ms);
+        if elapsed >= total {
+            self.frames = None;
+            return None;
+        }
+        self.frames = Some((effect, elapsed, total));
+
+        // Round up so that `sample_at` maps back to no earlier than `elapsed`.
+        let t = (u64::from(elapsed) << 16).div_ceil(u64::from(total));
+        Some(self.output(self.sample_at(effect, t.min(u16::MAX.into()) as u16)))
+    }
+
+    /// Render the effect prepared with `begin_frames` for at most `max_ms`
+    ///
+    /// For cooperative schedulers: unlike `next_frame`, the effect is
+    /// written to the pin and timed by the library, but each call waits no
+    /// more than `max_ms` in total before returning, and the next call
+    /// resumes where this one stopped, even in the middle of a frame. The
+    /// duty is updated every millisecond, or once per frame with `set_fps`
+    /// or `PowerMode::LowUpdate`. The waits go through the `set_yield` hook
+    /// and the watchdog as usual; since the effect's length is already taken
+    /// at the current speed, they are not scaled again or jittered. Once the
+    /// effect has run its full length the configured `EndState` is applied
+    /// and `Done` is returned; in verify mode a failed write ends it early.
+    /// A `max_ms` of `0` does nothing and returns `Pending` while an effect
+    /// is running.
+    pub fn render_slice(&mut self, max_ms: u32) -> RenderProgress {
+        let Some((effect, mut elapsed, total)) = self.frames else {
+            return RenderProgress::Done;
+        };
+        let frame_ms = self.frames_in(1_000).map_or(1, |fps| (1_000 / fps).max(1));
+
+        let mut left = max_ms;
+        while left > 0 {
+            let into_frame = elapsed % frame_ms;
+            if into_frame == 0 {
+                let t = (u64::from(elapsed) << 16).div_ceil(u64::from(total));
+                let duty = self.sample_at(effect, t.min(u16::MAX.into()) as u16);
+                self.write_duty(Self::to_u32(duty));
+            }
+
+            let dt = (frame_ms - into_frame).min(left).min(total - elapsed);
+            self.wait_ms(dt);
+            left -= dt;
+            elapsed += dt;
+            if elapsed >= total || self.fault {
+                self.frames = None;
+                let _ = self.finish();
+                return RenderProgress::Done;
+            }
+        }
+        self.frames = Some((effect, elapsed, total));
+        RenderProgress::Pending
+    }
+
+    /// Report the progress of the effect driven by `advance` or `next_frame`
+    ///
+    /// For an effect begun with `begin_frames`, `kind` names it, `progress`
+    /// is the share of its length pulled so far and `brightness` is that of
+    /// the last frame returned (before master dimming and inversion). For a
+    /// breath or fade begun with `begin_breath`/`begin_fade`, `kind` is
+    /// `None`, `progress` counts the steps taken and `brightness` is the
+    /// level on the pin. With nothing running, `progress` is `100`. Cheap
+    /// enough to call from a UI or telemetry loop between frames.
+    pub fn status(&self) -> EffectStatus {
+        if let Some((effect, elapsed, total)) = self.frames {
+            let t = (u64::from(elapsed) << 16).div_ceil(u64::from(total));
+            let duty = self.sample_at(effect, t.min(u16::MAX.into()) as u16);
+            return EffectStatus {
+                kind: Some(effect),
+                progress: (u64::from(elapsed) * 100 / u64::from(total)) as u8,
+                brightness: self.percent_of(duty),
+            };
+        }
+        let progress = match self.stepper {
+            Some(Stepper::Breath { step, total } | Stepper::Fade { step, total, .. }) => {
+                (u64::from(step) * 100 / u64::from(total)) as u8
+            }
+            None => 100,
+        };
+        EffectStatus {
+            kind: None,
+            progress,
+            brightness: self.brightness_percent(),
+        }
+    }
+
+    /// Make the brightness track an external input such as an ADC reading
+    ///
+    /// Calls `read` `samples` times, `interval_ms` apart. Each reading spans
+    /// `0..=65535` and is mapped linearly onto `[pwm_min, pwm_max]`. When a
+    /// slew limit is configured with `set_follow_slew`, the duty moves at
+    /// most that far towards each new reading, which smooths out a noisy
+    /// sensor, and readings within the deadband set with `set_deadband` of
+    /// the last accepted one are ignored. The LED is left at the last level
+    /// written.
+    pub fn follow(
+        &mut self,
+        mut read: impl FnMut() -> u16,
+        samples: u32,
+        interval_ms: u32,
+    ) -> Result<(), Error> {
+        let mut current: u32 = Self::to_u32(self.current_duty());
+
+        for _ in 0..samples {
+            let reading = self.debounced(read());
+            let target: u32 =
+                Self::to_u32(self.duty_from_fraction(reading.into(), u16::MAX.into()));
+            current = if self.follow_slew == 0 {
+                target
+            } else if target > current {
+                current.saturating_add(self.follow_slew).min(target)
+            } else {
+                current.saturating_sub(self.follow_slew).max(target)
+            };
+            self.write_duty(current);
+            self.delay_ms(interval_ms);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.flush();
+        self.complete()
+    }
+
+    /// Flash on beats signalled by external timing, e.g. music-reactive code
+    ///
+    /// For each of the `beats` beats, polls `next_beat` once per millisecond
+    /// until it returns `true`, then flashes to `pwm_max` and decays back to
+    /// `pwm_min` over `pulse_ms`. Between beats the LED rests at `pwm_min`.
+    /// The waits between polls go through the normal delay path, so with a
+    /// `set_yield` hook installed they are handed to the scheduler instead of
+    /// spinning. Keeps waiting for as long as `next_beat` does not fire. The
+    /// configured `EndState` is applied after the last beat.
+    pub fn beat_sync(
+        &mut self,
+        mut next_beat: impl FnMut() -> bool,
+        pulse_ms: u32,
+        beats: u32,
+    ) -> Result<(), Error> {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+
+        self.write_duty(min);
+        'beats: for _ in 0..beats {
+            while !next_beat() {
+                self.delay_ms(1);
+                if self.stopped() {
+                    break 'beats;
+                }
+            }
+            self.write_duty(max);
+            self.ramp(max, min, pulse_ms);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Create sparkle (twinkle) effect
+    ///
+    /// Over `duration_ms` the effect is split into short time slices. At each
+    /// slice a random roll decides whether to briefly flash to a random level
+    /// in the upper half of the range or to stay dim for one to four slices.
+    /// `density` sets the chance of a flash per slice (0 = never, 255 = almost
+    /// always). Randomness comes from the caller-supplied `rng`, so the effect
+    /// stays `no_std` and reproducible with a seeded generator.
+    pub fn sparkle<R: RngCore>(
+        &mut self,
+        rng: &mut R,
+        duration_ms: u32,
+        density: u8,
+    ) -> Result<(), Error> {
+        let mut elapsed = 0;
+        while elapsed < duration_ms {
+            let (level, slice) = self.sparkle_slice(rng.next_u32(), density, duration_ms - elapsed);
+            self.write_duty(level);
+            self.delay_ms(slice);
+            elapsed += slice;
+            if self.stopped() {
+                break;
+            }
+        }
+        self.finish()
+    }
+
+    /// Repeat `effect` until `total_ms` of cumulative delay has elapsed
+    ///
+    /// The effect is run back to back as many times as fits; the last
+    /// repetition is cut short as soon as the time budget is spent, after
+    /// which the configured `EndState` is applied as usual. An effect whose
+    /// parameters make it take no time at all is run only once.
+    pub fn run_for(&mut self, effect: EffectKind, total_ms: u32) -> Result<(), Error> {
+        self.budget_ms = Some(total_ms);
+        let result = loop {
+            let before = self.budget_ms;
+            if let Err(e) = self.run_kind(effect) {
+                break Err(e);
+            }
+            if self.stopped() || self.budget_ms == before {
+                break Ok(());
+            }
+        };
+        self.budget_ms = None;
+        result
+    }
+
+    /// Repeat `effect` for `secs` seconds
+    ///
+    /// Shorthand for `run_for(effect, secs * 1000)`, saturating at
+    /// `u32::MAX` ms.
+    pub fn run_for_secs(&mut self, effect: EffectKind, secs: u32) -> Result<(), Error> {
+        self.run_for(effect, secs_to_ms(secs))
+    }
+
+    /// Run `breath` until it completes or `cancel` is raised
+    ///
+    /// The flag is checked after every step, typically each millisecond or
+    /// so; once it is raised the cycle stops and the configured `EndState`
+    /// is applied as if it had completed. A flag that is already raised
+    /// stops the effect after its first step. See the `cancel` module.
+    pub fn breath_cancellable(
+        &mut self,
+        duration: u32,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        self.cancellable(cancel, |led| led.breath(duration))
+    }
+
+    /// Run `heartbeat` until it completes or `cancel` is raised
+    ///
+    /// Stops like `breath_cancellable`.
+    pub fn heartbeat_cancellable(
+        &mut self,
+        flash_beats: u32,
+        grouped_as: u32,
+        bpm: u32,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        self.cancellable(cancel, |led| led.heartbeat(flash_beats, grouped_as, bpm))
+    }
+
+    /// Run `fade` until it completes or `cancel` is raised
+    ///
+    /// A cancelled fade holds the level it had reached.
+    pub fn fade_cancellable(
+        &mut self,
+        target: PWM::Duty,
+        duration_ms: u32,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        self.cancellable(cancel, |led| led.fade(target, duration_ms))
+    }
+
+    /// Run `run_for` until its time is up or `cancel` is raised
+    ///
+    /// Covers every effect described by an `EffectKind`; stops like
+    /// `breath_cancellable`.
+    pub fn run_for_cancellable(
+        &mut self,
+        effect: EffectKind,
+        total_ms: u32,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        self.cancellable(cancel, |led| led.run_for(effect, total_ms))
+    }
+
+    /// Run `breath`, leaving `end` on the LED instead of the configured `EndState`
+    ///
+    /// Otherwise identical to `breath`; the setting from `set_end_state` is
+    /// untouched and applies again to the next call.
+    pub fn breath_with_end(&mut self, duration: u32, end: EndState) -> Result<(), Error> {
+        self.with_end(end, |led| led.breath(duration))
+    }
+
+    /// Run `heartbeat`, leaving `end` on the LED instead of the configured `EndState`
+    ///
+    /// See `breath_with_end`.
+    pub fn heartbeat_with_end(
+        &mut self,
+        flash_beats: u32,
+        grouped_as: u32,
+        bpm: u32,
+        end: EndState,
+    ) -> Result<(), Error> {
+        self.with_end(end, |led| led.heartbeat(flash_beats, grouped_as, bpm))
+    }
+
+    /// Run `run_for`, leaving `end` on the LED instead of the configured `EndState`
+    ///
+    /// Covers every effect described by an `EffectKind`; see `breath_with_end`.
+    pub fn run_for_with_end(
+        &mut self,
+        effect: EffectKind,
+        total_ms: u32,
+        end: EndState,
+    ) -> Result<(), Error> {
+        self.with_end(end, |led| led.run_for(effect, total_ms))
+    }
+
+    /// Blink a numeric error code, appliance style
+    ///
+    /// Each group blinks `code` times at `pwm_max` (200 ms on, 300 ms off)
+    /// and is followed by a 1.5 s pause; the LED shows the "off" duty (see
+    /// `set_off_duty`) between blinks and groups. The group is shown
+    /// `repeats` times, or forever if `repeats` is `0`, in which case this
+    /// only returns when a verified write fails. The configured `EndState` is
+    /// applied after the last group.
+    ///
+    /// Returns `Error::InvalidParameter` if `code` is zero.
+    pub fn fault_code(&mut self, code: u8, repeats: u32) -> Result<(), Error> {
+        if code == 0 {
+            return Err(Error::InvalidParameter);
+        }
+
+        let mut shown = 0;
+        'groups: while repeats == 0 || shown < repeats {
+            for _ in 0..code {
+                self.write_duty(Self::to_u32(self.pwm_max));
+                self.delay_ms(FAULT_ON_MS);
+                self.write_now(self.off_duty);
+                self.delay_ms(FAULT_OFF_MS);
+                if self.stopped() {
+                    break 'groups;
+                }
+            }
+            self.delay_ms(FAULT_PAUSE_MS);
+            if self.stopped() {
+                break;
+            }
+            shown = shown.saturating_add(1);
+        }
+        self.finish()
+    }
+
+    /// Blink SOS in Morse code until `clear` returns `true`
+    ///
+    /// A last-resort indicator for when something has already gone wrong:
+    /// dots are lit for 200 ms and dashes for 600 ms at `pwm_max`, with the
+    /// standard gaps and a 1.4 s pause before the pattern repeats. `clear`
+    /// is polled before every symbol. The timing is fixed and the pattern is
+    /// a table, so nothing here can overflow, and a failed verified write
+    /// does not stop the signal. Inversion, the safe maximum and the "off"
+    /// duty apply as usual, and the LED is left at the "off" duty.
+    pub fn distress(&mut self, mut clear: impl FnMut() -> bool) {
+        'sos: loop {
+            for (&on_ms, &gap_ms) in SOS_MS.iter().zip(SOS_GAP_MS.iter()) {
+                if clear() {
+                    break 'sos;
+                }
+                self.write_now(self.pwm_max);
+                self.delay_ms(on_ms);
+                self.write_now(self.off_duty);
+                self.delay_ms(gap_ms);
+            }
+        }
+        self.write_now(self.off_duty);
+        self.fault = false;
+    }
+
+    /// Preview the duty `effect` writes at a point of its run, without running it
+    ///
+    /// `t` spans the effect over `0..=65535`: one cycle of `Breath`
+    /// (starting at the phase set with `set_phase`), and the whole run of
+    /// every other kind. The result is the level the effect computes at
+    /// that moment, before the master brightness, safe maximum and
+    /// inversion are applied at the pin. Parameters the effect would reject
+    /// (such as a zero `bpm` or `levels`) yield `pwm_min`, as does a
+    /// `FaultCode` repeated forever. `Sparkle` and `OrganicBreath` are
+    /// replayed from their seed, so sampling late in a long run takes
+    /// longer.
+    pub fn sample_at(&self, effect: EffectKind, t: u16) -> PWM::Duty {
+        let scale = |total: u64| (total * u64::from(t)) >> 16;
+        let Some(length) = self.kind_length_ms(effect) else {
+            return self.pwm_min;
+        };
+        let ms = scale(length);
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+        let off = self.off_duty;
+        match effect {
+            EffectKind::Breath { duration } => {
+                let min: u32 = Self::to_u32(self.pwm_min);
+                let range = Self::to_u32(self.pwm_max) - min;
+                let third = self.breath_third(duration);
+                let steps = u64::from(3 * third);
+                let step = scale(steps) + u64::from(self.phase_shift(3 * third));
+                let position = (step % steps) as u32;
+                let offset = breath_offset(range, position, third) >> 8;
+                Self::from_u32(min + offset as u32)
+            }
+            EffectKind::Heartbeat {
+                flash_beats,
+                grouped_as,
+                bpm,
+            } => {
+                let total = self.heartbeat_duration_ms(flash_beats, grouped_as, bpm);
+                let ms = scale(u64::from(total)) * u64::from(self.speed) / 100;
+                self.heartbeat_sample(ms, grouped_as, bpm)
+            }
+            EffectKind::Staircase { levels, .. } => match levels {
+                0 => self.pwm_min,
+                1 => self.pwm_max,
+                _ => self.duty_from_fraction(scale(u64::from(levels)) as u32, levels - 1),
+            },
+            EffectKind::Fade {
+                from,
+                to,
+                duration_ms,
+            } => {
+                let from = self.level_duty(from).map_or(min, Self::to_u32);
+                let to = self.level_duty(to).map_or(min, Self::to_u32);
+                Self::from_u32(lerp(from, to, ms, duration_ms.into()))
+            }
+            EffectKind::Blink { on_ms, off_ms, .. } => {
+                let within = ms.checked_rem(u64::from(on_ms) + u64::from(off_ms));
+                if within.unwrap_or(0) < u64::from(on_ms) {
+                    self.pwm_max
+                } else {
+                    off
+                }
+            }
+            EffectKind::Throb {
+                attack_ms,
+                decay_ms,
+                ..
+            } => {
+                let (attack, decay) = (u64::from(attack_ms), u64::from(decay_ms));
+                let within = ms.checked_rem(attack + decay).unwrap_or(0);
+                let level = if within < attack {
+                    lerp(min, max, within, attack)
+                } else {
+                    lerp(max, min, within - attack, decay)
+                };
+                Self::from_u32(level)
+            }
+            EffectKind::Cycle { period_ms, .. } => {
+                let crossfade = u64::from(self.cycle_crossfade_ms.min(period_ms));
+                let rise = u64::from(period_ms) - crossfade;
+                let within = ms.checked_rem(period_ms.into()).unwrap_or(0);
+                let level = if within < rise {
+                    lerp(min, max, within, rise)
+                } else {
+                    lerp(max, min, within - rise, crossfade)
+                };
+                Self::from_u32(level)
+            }
+            EffectKind::Countdown { .. } => {
+                let remaining = (length - ms) * u64::from(u16::MAX) / length.max(1);
+                let level = remaining as u16;
+                let linear = if self.gamma { gamma::correct(level) } else { level };
+                self.duty_from_fraction(linear.into(), u16::MAX.into())
+            }
+            EffectKind::HeartbeatRealistic { bpm, .. } => {
+                let period = 60_000 / bpm;
+                let dub_peak: u32 = Self::to_u32(self.duty_from_fraction(3, 5));
+                let (rise, lub_decay) = (period / 20, period / 8);
+                let (gap, dub_decay) = (period / 16, period / 6);
+                let phases = [
+                    (min, max, rise),
+                    (max, min, lub_decay),
+                    (min, min, gap),
+                    (min, dub_peak, rise),
+                    (dub_peak, min, dub_decay),
+                ];
+                let mut within = ms.checked_rem(period.into()).unwrap_or(0);
+                for (from, to, phase_ms) in phases {
+                    if within < u64::from(phase_ms) {
+                        return Self::from_u32(lerp(from, to, within, phase_ms.into()));
+                    }
+                    within -= u64::from(phase_ms);
+                }
+                self.pwm_min
+            }
+            EffectKind::LayeredBreath {
+                small_cycles,
+                small_low,
+                ..
+            } => {
+                let low = self.level_duty(small_low).map_or(min, Self::to_u32);
+                let small_ms = u64::from(small_cycles) * u64::from(LAYERED_SMALL_MS);
+                let small = ms < small_ms;
+                let (ms, period, bottom) = if small {
+                    (ms, u64::from(LAYERED_SMALL_MS), low)
+                } else {
+                    (ms - small_ms, u64::from(LAYERED_BIG_MS), min)
+                };
+                let (breath, within) = (ms / period, ms % period);
+                // Each breath rises from where the one before it bottomed out.
+                let from = match (small, breath) {
+                    (true, 0) => min,
+                    (true, _) => low,
+                    (false, 0) if small_cycles > 0 => low,
+                    (false, _) => min,
+                };
+                let level = if within < period / 2 {
+                    lerp(from, max, within, period / 2)
+                } else {
+                    lerp(max, bottom, within - period / 2, period - period / 2)
+                };
+                Self::from_u32(level)
+            }
+            EffectKind::Sparkle {
+                duration_ms,
+                density,
+                seed,
+            } => {
+                let mut rng = Seeded::new(seed);
+                let mut elapsed = 0;
+                while elapsed < duration_ms {
+                    let (level, slice) =
+                        self.sparkle_slice(rng.next_u32(), density as u8, duration_ms - elapsed);
+                    elapsed += slice;
+                    if ms < u64::from(elapsed) {
+                        return Self::from_u32(level);
+                    }
+                }
+                self.pwm_min
+            }
+            EffectKind::OrganicBreath { seed, .. } => {
+                let mut rng = Seeded::new(seed);
+                let mut start = 0;
+                loop {
+                    let (period, amplitude) = organic_cycle(rng.next_u32(), u64::from(max - min));
+                    if ms < start + u64::from(period) {
+                        let t = (ms - start) as u32;
+                        let t = t - t % ORGANIC_FRAME_MS;
+                        return Self::from_u32(min + organic_offset(amplitude, t, period));
+                    }
+                    start += u64::from(period);
+                }
+            }
+            EffectKind::FaultCode { code, .. } => {
+                let blink = u64::from(FAULT_ON_MS + FAULT_OFF_MS);
+                let group = u64::from(code) * blink + u64::from(FAULT_PAUSE_MS);
+                let within = ms % group;
+                if within < u64::from(code) * blink && within % blink < u64::from(FAULT_ON_MS) {
+                    self.pwm_max
+                } else {
+                    off
+                }
+            }
+            EffectKind::Distress { .. } => {
+                let pattern: u32 = SOS_MS.iter().chain(SOS_GAP_MS.iter()).sum();
+                let mut within = ms % u64::from(pattern);
+                for (&on_ms, &gap_ms) in SOS_MS.iter().zip(SOS_GAP_MS.iter()) {
+                    if within < u64::from(on_ms) {
+                        return self.pwm_max;
+                    }
+                    if within < u64::from(on_ms + gap_ms) {
+                        break;
+                    }
+                    within -= u64::from(on_ms + gap_ms);
+                }
+                off
+            }
+        }
+    }
+
+    /// Play a short startup animation that never takes longer than `total_ms`
+    ///
+    /// Fades in from `pwm_min` to `pwm_max` over the first 30% of the time,
+    /// pulses twice between `pwm_max` and `pwm_mid` over the next 40% and
+    /// settles to `pwm_mid` over the rest. The whole sequence runs under a
+    /// time budget like `run_for`, so timing rounding, jitter or a slowed
+    /// down speed truncate the last phase instead of overrunning.
+    ///
+    /// Always leaves the LED glowing steadily at `pwm_mid`, regardless of
+    /// the configured `EndState`.
+    pub fn boot_sequence(&mut self, total_ms: u32) -> Result<(), Error> {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let max: u32 = Self::to_u32(self.pwm_max);
+        let mid: u32 = Self::to_u32(self.pwm_mid);
+        let fade_in = (u64::from(total_ms) * 3 / 10) as u32;
+        let pulse = total_ms / 10;
+        let settle = total_ms - fade_in - 4 * pulse;
+
+        let phases = [
+            (min, max, fade_in),
+            (max, mid, pulse),
+            (mid, max, pulse),
+            (max, mid, pulse),
+            (mid, max, pulse),
+            (max, mid, settle),
+        ];
+
+        self.budget_ms = Some(total_ms);
+        self.write_duty(min);
+        for (from, to, duration_ms) in phases {
+            if self.stopped() {
+                break;
+            }
+            self.ramp(from, to, duration_ms);
+        }
+        self.budget_ms = None;
+
+        self.write_now(self.pwm_mid);
+        self.complete()
+    }
+
+    /// Run `effect` once, dispatching to the matching effect method
+    ///
+    /// Lets an application keep "the selected effect" as a plain
+    /// `EffectKind` value in its own state, change it in response to events
+    /// and run it from its main loop, without trait objects:
+    ///
+    /// ```ignore
+    /// app.effect = EffectKind::Heartbeat { flash_beats: 2, grouped_as: 1, bpm: 60 };
+    /// led.run_kind(app.effect)?;
+    /// ```
+    ///
+    /// Brightness levels are mapped onto `[pwm_min, pwm_max]` and seeded
+    /// kinds get a fresh generator from their seed, so the same kind always
+    /// plays the same way. Fails exactly when the effect method itself
+    /// would, and with `Error::InvalidParameter` for a level, `density` or
+    /// `code` too large for it.
+    pub fn run_kind(&mut self, effect: EffectKind) -> Result<(), Error> {
+        match effect {
+            EffectKind::Breath { duration } => self.breath(duration),
+            EffectKind::Heartbeat {
+                flash_beats,
+                grouped_as,
+                bpm,
+            } => self.heartbeat(flash_beats, grouped_as, bpm),
+            EffectKind::Staircase { levels, hold_ms } => self.staircase(levels, hold_ms),
+            EffectKind::Fade {
+                from,
+                to,
+                duration_ms,
+            } => {
+                let from = self.level_duty(from).ok_or(Error::InvalidParameter)?;
+                let to = self.level_duty(to).ok_or(Error::InvalidParameter)?;
+                self.fade_between(from, to, duration_ms)
+            }
+            EffectKind::Blink {
+                on_ms,
+                off_ms,
+                times,
+            } => self.blink(on_ms, off_ms, times),
+            EffectKind::Throb {
+                attack_ms,
+                decay_ms,
+                cycles,
+            } => self.throb(attack_ms, decay_ms, cycles),
+            EffectKind::Cycle { period_ms, cycles } => self.cycle(period_ms, cycles),
+            EffectKind::Countdown { total_ms } => self.countdown(total_ms),
+            EffectKind::HeartbeatRealistic { bpm, beats } => self.heartbeat_realistic(bpm, beats),
+            EffectKind::LayeredBreath {
+                small_cycles,
+                small_low,
+                big_cycles,
+            } => {
+                let low = self.level_duty(small_low).ok_or(Error::InvalidParameter)?;
+                self.layered_breath(small_cycles, low, big_cycles)
+            }
+            EffectKind::Sparkle {
+                duration_ms,
+                density,
+                seed,
+            } => {
+                let density = u8::try_from(density).map_err(|_| Error::InvalidParameter)?;
+                self.sparkle(&mut Seeded::new(seed), duration_ms, density)
+            }
+            EffectKind::OrganicBreath { duration_ms, seed } => {
+                self.organic_breath(&mut Seeded::new(seed), duration_ms)
+            }
+            EffectKind::FaultCode { code, repeats } => {
+                let code = u8::try_from(code).map_err(|_| Error::InvalidParameter)?;
+                self.fault_code(code, repeats)
+            }
+            EffectKind::Distress { repeats } => {
+                // `distress` polls before every symbol; stop it after one pattern.
+                for _ in 0..repeats {
+                    let mut polls = 0;
+                    self.distress(|| {
+                        polls += 1;
+                        polls > SOS_MS.len()
+                    });
+                    if self.stopped() {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The brightness duty most recently written, before dimming and inversion
+    ///
+    /// Starts out as whatever the pin reported when the effect was created.
+    #[inline(always)]
+    pub fn current_duty(&self) -> PWM::Duty {
+        self.duty
+    }
+
+    /// Phase offset at which breathing cycles start (`0..=65535` = 0..360°)
+    pub fn phase(&self) -> u16 {
+        self.phase
+    }
+
+    /// Shift where in its cycle `breath` (and `begin_breath`) starts
+    ///
+    /// `phase` spans a full cycle over `0..=65535`, i.e. `16384` is a quarter
+    /// cycle and `32768` half a cycle. Two LEDs breathing with phases `0`
+    /// and `32768` run in counter-phase: one is bright while the other is
+    /// dim. Defaults to `0`, starting at `pwm_min`.
+    pub fn set_phase(&mut self, phase: u16) {
+        self.phase = phase;
+    }
+
+    /// Largest duty change per sample made by `follow` (`0` = unlimited)
+    pub fn follow_slew(&self) -> u32 {
+        self.follow_slew
+    }
+
+    /// Limit how far `follow` moves the duty per sample
+    ///
+    /// `0` (the default) jumps straight to each reading.
+    pub fn set_follow_slew(&mut self, max_step: u32) {
+        self.follow_slew = max_step;
+    }
+
+    /// Smallest input change `follow` and `set_progress` react to (`0` = any)
+    pub fn deadband(&self) -> u16 {
+        self.deadband
+    }
+
+    /// Ignore input changes of up to `band` in `follow` and `set_progress`
+    ///
+    /// A noisy sensor or a value hovering at a threshold otherwise makes the
+    /// LED flick between two levels. With a deadband, a new input is only
+    /// accepted once it differs from the last accepted one by more than
+    /// `band`, on the `0..=65535` input scale (one percent of progress is
+    /// about `655`). `0` (the default) accepts every change.
+    pub fn set_deadband(&mut self, band: u16) {
+        self.deadband = band;
+        self.last_input = None;
+    }
+
+    /// Portion of each `cycle` period spent fading back to `pwm_min`, in milliseconds
+    pub fn cycle_crossfade_ms(&self) -> u32 {
+        self.cycle_crossfade_ms
+    }
+
+    /// Soften the wrap of `cycle` by fading back down over `crossfade_ms`
+    ///
+    /// `0` (the default) snaps from `pwm_max` straight back to `pwm_min`.
+    /// The crossfade is taken out of the cycle period, so the total length of
+    /// `cycle` is unchanged.
+    pub fn set_cycle_crossfade_ms(&mut self, crossfade_ms: u32) {
+        self.cycle_crossfade_ms = crossfade_ms;
+    }
+
+    /// Extra time `breath` spends at `pwm_min` at the bottom of each cycle, in milliseconds
+    pub fn trough_hold_ms(&self) -> u32 {
+        self.trough_hold_ms
+    }
+
+    /// Dwell at `pwm_min` for `hold_ms` when `breath` reaches its trough
+    ///
+    /// Some LED drivers have a minimum on-time and strobe visibly at the
+    /// smallest duties just above `pwm_min`. A short dwell at the trough
+    /// hides the end of the fall behind a steady level. The dwell is added
+    /// on top of the cycle duration. `0` (the default) disables it.
+    pub fn set_trough_hold_ms(&mut self, hold_ms: u32) {
+        self.trough_hold_ms = hold_ms;
+    }
+
+    /// Highest duty ever written to the pin (`pwm_max` unless capped)
+    pub fn safe_max(&self) -> PWM::Duty {
+        self.safe_max
+    }
+
+    /// Cap the duty written to the pin at `cap` for thermal or current limiting
+    ///
+    /// Effects keep computing their levels over the full `[pwm_min, pwm_max]`
+    /// range; only the value sent to the pin is limited, so a `breath` is
+    /// flattened at `cap` rather than rescaled. The cap applies after the
+    /// master brightness and to `on` and `set_level` as well.
+    ///
+    /// Returns `Error::InvalidParameter` if `cap` lies outside
+    /// `[pwm_min, pwm_max]`.
+    pub fn set_safe_max(&mut self, cap: PWM::Duty) -> Result<(), Error> {
+        if cap < self.pwm_min || cap > self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+        self.safe_max = cap;
+        Ok(())
+    }
+
+    /// Duty written when the LED is turned "off"
+    pub fn off_duty(&self) -> PWM::Duty {
+        self.off_duty
+    }
+
+    /// Set the duty written when the LED is turned "off"
+    ///
+    /// Used by `off`, `sleep`, `EndState::Off` and the dark phases of
+    /// `fault_code`. Defaults to `0`; set it to `pwm_min` for a driver whose
+    /// lowest legal level is not zero. The value is a brightness like any
+    /// other, so inversion still applies when it is written.
+    ///
+    /// Returns `Error::InvalidParameter` if `duty` is above `pwm_min`, which
+    /// would make "off" brighter than the dimmest animated level.
+    pub fn set_off_duty(&mut self, duty: PWM::Duty) -> Result<(), Error> {
+        if duty > self.pwm_min {
+            return Err(Error::InvalidParameter);
+        }
+        self.off_duty = duty;
+        Ok(())
+    }
+
+    /// Smallest non-zero duty written to the pin
+    pub fn min_on(&self) -> PWM::Duty {
+        self.min_on
+    }
+
+    /// Lift every non-zero duty below `threshold` up to `threshold`
+    ///
+    /// For drivers with a minimum pulse width, where small duties give no
+    /// light or flicker. Lit levels that fall into that dead zone, e.g. at
+    /// the bottom of a fade or after master dimming, are written as
+    /// `threshold` instead; a duty of `0` stays fully off. `0` (the
+    /// default) disables the lift.
+    ///
+    /// This complements `pwm_min`, the floor of the effects' range: raising
+    /// `pwm_min` squeezes every effect into a narrower range, while the
+    /// minimum on-time only replaces the levels below `threshold` and
+    /// leaves the shape of the effects above it untouched. With `pwm_min`
+    /// at or above `threshold` it has no effect. The lift applies before
+    /// the safe maximum and inversion, and not to `set_duty_raw`.
+    ///
+    /// Returns `Error::InvalidParameter` if `threshold` is above `pwm_max`.
+    pub fn set_min_on(&mut self, threshold: PWM::Duty) -> Result<(), Error> {
+        if threshold > self.pwm_max {
+            return Err(Error::InvalidParameter);
+        }
+        self.min_on = threshold;
+        Ok(())
+    }
+
+    /// Master brightness scale applied to every effect
+    pub fn master(&self) -> Percent {
+        self.master
+    }
+
+    /// Set a master brightness that dims every effect uniformly
+    ///
+    /// Acts like a dimmer over the whole animation: every duty an effect
+    /// produces is scaled by `scale` before it reaches the pin. Only
+    /// the part above `pwm_min` is scaled, so the dimmest level stays at
+    /// `pwm_min` and the result never leaves the configured range. The
+    /// scale applies after gamma correction, i.e. it dims linear intensity.
+    /// `Percent::FULL` (the default) leaves effects untouched;
+    /// `Percent::ZERO` holds every lit level at `pwm_min`.
+    pub fn set_master(&mut self, scale: Percent) {
+        self.master = scale;
+    }
+
+    /// Frame rate of the smooth effects, if fixed with `set_fps`
+    pub fn fps(&self) -> Option<u16> {
+        self.fps
+    }
+
+    /// Render the smooth effects at a fixed `fps` frames per second
+    ///
+    /// By default the smooth effects (`breath`, `fade` and the ramps of the
+    /// other effects) pick their number of steps from the duty range, which
+    /// gives few, visible steps on low-resolution timers and a flood of
+    /// sub-millisecond updates on high-resolution ones. With a frame rate
+    /// set, an animation lasting `duration_ms` is drawn in
+    /// `duration_ms * fps / 1000` frames (at least one), interpolating the
+    /// duty across them; the total duration is unchanged. Takes precedence
+    /// over the per-millisecond updates of `set_dither`.
+    ///
+    /// Returns `Error::InvalidParameter` if `fps` is zero.
+    pub fn set_fps(&mut self, fps: u16) -> Result<(), Error> {
+        if fps == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        self.fps = Some(fps);
+        Ok(())
+    }
+
+    /// Go back to picking the number of steps from the duty range
+    pub fn clear_fps(&mut self) {
+        self.fps = None;
+    }
+
+    /// Whether temporal dithering is enabled
+    pub fn dither(&self) -> bool {
+        self.dither
+    }
+
+    /// Enable or disable temporal dithering in the smooth effects
+    ///
+    /// On low-resolution timers the slow, dim parts of a fade or `breath`
+    /// show visible steps. With dithering the smooth effects update the
+    /// duty every millisecond and alternate between the two duties around
+    /// each intermediate level, so the eye averages them into a level in
+    /// between. This only works if the PWM frequency is well above 1 kHz and
+    /// writes are not coalesced away (leave `set_min_update_ms` at `0`).
+    /// Off by default.
+    pub fn set_dither(&mut self, dither: bool) {
+        self.dither = dither;
+        self.dither_error = 0;
+    }
+
+    /// Whether `set_level` applies gamma correction
+    pub fn gamma(&self) -> bool {
+        self.gamma
+    }
+
+    /// Turn gamma correction in `set_level` on (the default) or off
+    ///
+    /// With gamma off, `set_level` maps its level linearly onto the range,
+    /// for drivers or diffusers that already compensate perception.
+    pub fn set_gamma(&mut self, gamma: bool) {
+        self.gamma = gamma;
+    }
+
+    /// The effect stored as this LED's default, if any
+    pub fn default_effect(&self) -> Option<EffectKind> {
+        self.default_effect
+    }
+
+    /// Remember `effect` as this LED's default
+    ///
+    /// The crate does not run it on its own; it is stored so it can be
+    /// persisted with `to_config` and restored with `from_config`, and run
+    /// with `run_for` after boot.
+    pub fn set_default_effect(&mut self, effect: Option<EffectKind>) {
+        self.default_effect = effect;
+    }
+
+    /// Whether the output is inverted (active-low LED)
+    pub fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Invert the output for LEDs wired active-low or behind an inverting buffer
+    ///
+    /// All effects keep working in terms of brightness; only the value sent
+    /// to the pin is mirrored as `get_max_duty() - duty`, so an "off" duty
+    /// of `0` drives the pin fully high.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    /// Whether every duty write is read back and verified
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+
+    /// Enable or disable read-back verification of duty writes
+    ///
+    /// In verify mode every `set_duty` is followed by a `get_duty`; if the
+    /// pin does not report the value just written, the running effect stops
+    /// and returns `Error::Pwm`. This detects a wedged PWM peripheral for
+    /// safety-critical indicators. Off by default, so normal effects do not
+    /// pay for the extra read.
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Minimum interval, in milliseconds, between two duty writes of an effect
+    pub fn min_update_ms(&self) -> u32 {
+        self.min_update_ms
+    }
+
+    /// Set the minimum interval, in milliseconds, between two duty writes
+    ///
+    /// Some PWM peripherals only latch a new duty on the timer's period
+    /// boundary and flicker when the register is rewritten faster than that.
+    /// With a non-zero interval, effect updates that arrive sooner are
+    /// coalesced: only the most recent value is written once the interval
+    /// has passed, and the final value of an effect is always written. The
+    /// overall timing of effects is unchanged. `0` (the default) writes every
+    /// update immediately.
+    pub fn set_min_update_ms(&mut self, min_update_ms: u32) {
+        self.min_update_ms = min_update_ms;
+    }
+
+    /// Smallest perceptual change, in gamma levels, that the effects write
+    pub fn jnd(&self) -> u8 {
+        self.jnd
+    }
+
+    /// Skip duty writes that would not be visibly different
+    ///
+    /// Brightness is compared on the 256-level gamma 2.2 scale of
+    /// `gamma::GAMMA_2_2`: an effect update less than `steps` levels away
+    /// from the last value written is held back instead of written. Since
+    /// the eye is far more sensitive near black, small duty changes still
+    /// get through at the bottom of a fade while most of the writes near
+    /// the top are dropped. The last value of an effect is always written,
+    /// and the timing of effects is unchanged. `0` (the default) writes
+    /// every update.
+    pub fn set_jnd(&mut self, steps: u8) {
+        self.jnd = steps;
+    }
+
+    /// Largest duty change written per millisecond, `0` if unlimited
+    pub fn max_slew(&self) -> u32 {
+        self.max_slew
+    }
+
+    /// Limit every duty change to `duty_per_ms` per millisecond
+    ///
+    /// A global smoothing policy on top of the effects: whenever a write
+    /// would move the duty further than `duty_per_ms` from the last value,
+    /// whether from an effect step, `on`, `off`, `set_level` or an end
+    /// state, a short ramp is inserted that moves `duty_per_ms` per
+    /// millisecond. This protects eyes from harsh jumps and drivers from
+    /// inrush. Effects already slower than the limit are unaffected; faster
+    /// ones take longer by the time the inserted ramps need, which is not
+    /// scaled by `set_speed` or counted against a `run_for` budget. Writes
+    /// from `set_duty_raw` and `advance` are never slewed, the latter so
+    /// that it stays safe to call from an interrupt. `0` (the default)
+    /// writes every change at once.
+    pub fn set_max_slew(&mut self, duty_per_ms: u32) {
+        self.max_slew = duty_per_ms;
+    }
+
+    /// Whether the PWM output is disabled while the LED is dark
+    pub fn auto_disable(&self) -> bool {
+        self.auto_disable
+    }
+
+    /// Disable the PWM output whenever the LED is dark
+    ///
+    /// When on, writing a dark level (duty `0`, or the pin's maximum when
+    /// inverted) writes it once and then calls `disable` on the pin. The
+    /// pin stays disabled, with no further writes, until a non-dark level
+    /// comes along, which calls `enable` before writing it. Meant for
+    /// battery-powered devices that spend most of their time with the LED
+    /// off: a disabled channel stops driving its pin and, on parts that
+    /// gate the timer clock per channel, stops the counter too. No idle
+    /// current figure is given here because none has been measured yet; the
+    /// saving depends on the microcontroller and its HAL, so measure it on
+    /// the target board before relying on it. With a non-zero `pwm_min` only the
+    /// "off" duty and `off` are dark; the low points of an effect keep the
+    /// output running. Turning this off re-enables a disabled output.
+    pub fn set_auto_disable(&mut self, on: bool) {
+        self.auto_disable = on;
+        if !on {
+            self.resume();
+        }
+    }
+
+    /// What the effects leave on the LED when they finish
+    pub fn end_state(&self) -> EndState {
+        self.end_state
+    }
+
+    /// Choose what the effects leave on the LED when they finish
+    ///
+    /// Defaults to `EndState::Off`. Use `EndState::Min` or `EndState::Hold`
+    /// to chain effects without a black flash in between. `fade` always
+    /// holds its target and is not affected.
+    pub fn set_end_state(&mut self, end_state: EndState) {
+        self.end_state = end_state;
+    }
+
+    /// How eagerly the smooth effects update the LED
+    pub fn power_mode(&self) -> PowerMode {
+        self.power_mode
+    }
+
+    /// Trade animation smoothness for fewer duty writes and longer waits
+    ///
+    /// In `PowerMode::LowUpdate` the smooth effects (`breath`, `fade` and the
+    /// ramps of the other effects) are drawn at 25 frames per second instead
+    /// of up to one step per millisecond, and dithering is skipped. That cuts
+    /// the `set_duty` writes of a slow fade by an order of magnitude and
+    /// hands the `set_yield` hook waits of 40 ms, long enough for the MCU to
+    /// sleep in between. The cost is visible: slow, dim fades show distinct
+    /// steps on low-resolution timers, and fast ramps look coarser. A rate
+    /// set with `set_fps` still takes precedence, and the total duration of
+    /// every effect is unchanged. `PowerMode::Normal` is the default.
+    pub fn set_power_mode(&mut self, mode: PowerMode) {
+        self.power_mode = mode;
+    }
+
+    /// Core clock frequency, in Hz, assumed by the busy-wait delay
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    /// Set the core clock frequency, in Hz, used to time the busy-wait delay
+    ///
+    /// Returns `Error::InvalidParameter` for frequencies below 1 kHz, which
+    /// cannot express a single millisecond in clock cycles.
+    pub fn set_clock_hz(&mut self, clock_hz: u32) -> Result<(), Error> {
+        if clock_hz < MIN_CLOCK_HZ {
+            return Err(Error::InvalidParameter);
+        }
+        self.clock_hz = clock_hz;
+        Ok(())
+    }
+
+    /// Call `kick` at least every `interval_ms` while effects are waiting
+    ///
+    /// Long effects busy-wait for seconds at a time, which would trip a
+    /// hardware watchdog. With a hook installed, every delay is split into
+    /// chunks and `kick` runs each time `interval_ms` of waiting has passed
+    /// (counted across consecutive delays), so it can feed the watchdog.
+    /// The hook may capture state, such as the watchdog peripheral, and is
+    /// borrowed for as long as the effect lives:
+    ///
+    /// ```ignore
+    /// let mut kick = || watchdog.feed();
+    /// led.set_watchdog(&mut kick, 500)?;
+    /// ```
+    ///
+    /// It must be `Send` so that the effect can still move between tasks,
+    /// e.g. as an RTIC resource.
+    ///
+    /// Returns `Error::InvalidParameter` if `interval_ms` is zero.
+    pub fn set_watchdog(
+        &mut self,
+        kick: &'a mut (dyn FnMut() + Send),
+        interval_ms: u32,
+    ) -> Result<(), Error> {
+        if interval_ms == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        self.watchdog = Some((kick, interval_ms));
+        self.since_kick_ms = 0;
+        Ok(())
+    }
+
+    /// Remove the watchdog hook installed with `set_watchdog`
+    pub fn clear_watchdog(&mut self) {
+        self.watchdog = None;
+    }
+
+    /// Animation speed in percent of normal (`100` = normal)
+    pub fn speed(&self) -> u16 {
+        self.speed
+    }
+
+    /// Speed up or slow down every effect without changing its arguments
+    ///
+    /// `scale_percent` is the speed in percent: `200` runs effects twice as
+    /// fast (halving every delay), `50` at half speed. Delays are scaled as
+    /// they are made, carrying the rounding remainder from one to the next,
+    /// so long effects keep their scaled length exactly. Returns
+    /// `Error::InvalidParameter` for `0`, which would freeze every effect.
+    pub fn set_speed(&mut self, scale_percent: u16) -> Result<(), Error> {
+        if scale_percent == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        self.speed = scale_percent;
+        self.speed_carry = 0;
+        Ok(())
+    }
+
+    /// Randomly lengthen or shorten every delay by up to `max_jitter_ms`
+    ///
+    /// Makes fixed-period effects such as `breath` and `heartbeat` look less
+    /// mechanical. `rng` is only used to seed a small internal generator, so
+    /// it does not need to outlive this call. Each non-zero delay is moved
+    /// by a random amount in `-max_jitter_ms..=max_jitter_ms`, but never
+    /// below zero. `0` disables jitter.
+    pub fn set_jitter<R: RngCore>(&mut self, rng: &mut R, max_jitter_ms: u32) {
+        self.jitter = if max_jitter_ms == 0 {
+            None
+        } else {
+            Some((rng.next_u32() | 1, max_jitter_ms))
+        };
+    }
+
+    /// Remove the timing jitter set with `set_jitter`
+    pub fn clear_jitter(&mut self) {
+        self.jitter = None;
+    }
+
+    /// Hand every wait to `wait` instead of busy-waiting
+    ///
+    /// Under an RTOS or RTIC a busy-wait starves other tasks. With a hook
+    /// installed, effects call `wait(ms)` for each delay so it can be passed
+    /// to the scheduler (`vTaskDelay`, a timer delay, ...). Zero-length waits
+    /// are skipped, and a watchdog hook still splits waits into chunks. Like
+    /// the watchdog, the hook may capture state, is borrowed for as long as
+    /// the effect lives and must be `Send`.
+    pub fn set_yield(&mut self, wait: &'a mut (dyn FnMut(u32) + Send)) {
+        self.yield_hook = Some(wait);
+    }
+
+    /// Remove the hook installed with `set_yield` and busy-wait again
+    pub fn clear_yield(&mut self) {
+        self.yield_hook = None;
+    }
+
+    /// Report the final brightness to `settle` whenever an effect finishes
+    ///
+    /// Called with the brightness the LED is left at, as a percentage of
+    /// `[pwm_min, pwm_max]` (`0` for `pwm_min` and anything below it, such
+    /// as "off"), each time an effect or setter completes successfully.
+    /// Effects that never finish, and ones that fail, do not report. Store
+    /// the value in non-volatile memory and restore it on the next boot
+    /// with `set_progress(saved, 0)`. Like the other hooks, `settle` may
+    /// capture state, such as the storage driver, is borrowed for as long
+    /// as the effect lives and must be `Send`.
+    pub fn on_settle(&mut self, settle: &'a mut (dyn FnMut(u8) + Send)) {
+        self.settle_hook = Some(settle);
+    }
+
+    /// Remove the hook installed with `on_settle`
+    pub fn clear_settle(&mut self) {
+        self.settle_hook = None;
+    }
+
+    /// Measure the real core clock against a known-good delay and store it
+    ///
+    /// SysTick is run from the core clock while `reference` waits for a fixed
+    /// 10 ms; the number of elapsed core cycles gives the actual frequency,
+    /// which replaces the stored `clock_hz` and is returned. `reference` must
+    /// not itself be built on SysTick (use a timer-based delay instead).
+    ///
+    /// SysTick is left disabled afterwards. Returns `Error::InvalidParameter`
+    /// if the 24-bit counter wrapped during the measurement (core clock above
+    /// ~1.6 GHz) or the measured frequency is implausibly low.
+    pub fn calibrate<D: DelayMs<u32>>(
+        &mut self,
+        syst: &mut SYST,
+        reference: &mut D,
+    ) -> Result<u32, Error> {
+        syst.disable_counter();
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(0x00FF_FFFF);
+        syst.clear_current();
+        syst.enable_counter();
+
+        let start = SYST::get_current();
+        reference.delay_ms(CALIBRATION_MS);
+        let end = SYST::get_current();
+        let wrapped = syst.has_wrapped();
+        syst.disable_counter();
+
+        if wrapped {
+            return Err(Error::InvalidParameter);
+        }
+
+        let cycles = start.wrapping_sub(end) & 0x00FF_FFFF;
+        let clock_hz = cycles * (1_000 / CALIBRATION_MS);
+        self.set_clock_hz(clock_hz)?;
+        Ok(clock_hz)
+    }
+
+    /// Destroy the LED effect instance and return the underlying pin
+    pub fn destroy(self) -> PWM {
+        self.pin
+    }
+
+    /// Number of steps in each third of a `breath` cycle lasting `duration` ms.
+    #[inline(always)]
+    fn breath_third(&self, duration: u32) -> u32 {
+        let range = Self::to_u32(self.pwm_max) - Self::to_u32(self.pwm_min);
+        if let Some(frames) = self.frames_in(duration) {
+            return (frames / 3).max(1);
+        }
+        if self.dither { duration / 3 } else { range.min(duration / 3) }.max(1)
+    }
+
+    /// Number of frames in `duration_ms` at a fixed frame rate, if any.
+    ///
+    /// The rate is the one set with `set_fps`, or the low-power rate in
+    /// `PowerMode::LowUpdate`.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration_ms` - The length of the animation in milliseconds.
+    #[inline(always)]
+    fn frames_in(&self, duration_ms: u32) -> Option<u32> {
+        let fps = match (self.fps, self.power_mode) {
+            (Some(fps), _) => fps,
+            (None, PowerMode::LowUpdate) => LOW_UPDATE_FPS,
+            (None, PowerMode::Normal) => return None,
+        };
+        Some(((u64::from(duration_ms) * u64::from(fps) / 1_000) as u32).max(1))
+    }
+
+    /// Duty `heartbeat` writes `ms` milliseconds after it started.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The time since the start of the effect, in milliseconds.
+    /// * `grouped_as` - The number of beats per group.
+    /// * `bpm` - The beats per minute.
+    fn heartbeat_sample(&self, ms: u64, grouped_as: u32, bpm: u32) -> PWM::Duty {
+        let Some((period_time, short_period_time, decay_time)) = self.heartbeat_timing(bpm)
+        else {
+            return self.pwm_min;
+        };
+        let decay_steps = self.heartbeat_decay_steps(decay_time);
+        let short = u64::from(short_period_time);
+        let decay = u64::from(decay_time);
+        let beat = short * 3 + decay + u64::from(period_time);
+
+        // Find the time within the current beat, skipping whole groups first.
+        let mut ms = ms;
+        if grouped_as > 0 {
+            let extra = u64::from(heartbeat::wait(grouped_as, grouped_as, period_time))
+                - u64::from(period_time);
+            ms %= u64::from(grouped_as) * beat + extra;
+        }
+        if beat == 0 {
+            return self.pwm_max;
+        }
+        if grouped_as > 0 && ms / beat >= u64::from(grouped_as) {
+            // The extra wait after the last beat of a group
+            return self.pwm_min;
+        }
+        let within = ms % beat;
+
+        if within < short {
+            self.pwm_max
+        } else if within < short * 3 {
+            self.pwm_min
+        } else {
+            // Step `k` of the decay is written `decay * k / decay_steps` ms in.
+            let step = ((within - short * 3 + 1) * decay_steps)
+                .div_ceil(decay.max(1))
+                .saturating_sub(1);
+            let step = if decay == 0 { decay_steps } else { step };
+            if step < decay_steps {
+                Self::from_u32(self.heartbeat_decay_level(step, decay_steps))
+            } else {
+                self.pwm_min
+            }
+        }
+    }
+
+    /// Length of a `heartbeat` at normal speed, or `None` if `bpm` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `flash_beats` - The number of beats to flash.
+    /// * `grouped_as` - The number of beats per group.
+    /// * `bpm` - The beats per minute.
+    fn heartbeat_length_ms(&self, flash_beats: u32, grouped_as: u32, bpm: u32) -> Option<u64> {
+        let (period_time, short_period_time, decay_time) = self.heartbeat_timing(bpm)?;
+        let beat = u64::from(short_period_time) * 3 + u64::from(decay_time);
+
+        // Every beat ends with at least `period_time`; the last of each group
+        // waits as much again as `heartbeat::wait` adds on top.
+        let groups = flash_beats.checked_div(grouped_as).unwrap_or(0);
+        let group_extra = u64::from(heartbeat::wait(grouped_as, grouped_as, period_time))
+            - u64::from(period_time);
+        Some(
+            u64::from(flash_beats) * (beat + u64::from(period_time))
+                + u64::from(groups) * group_extra,
+        )
+    }
+
+    /// Length of `effect` at normal speed, in milliseconds.
+    ///
+    /// `Breath` counts a single cycle without the trough dwell. Returns
+    /// `None` for parameters the effect would reject and for a `FaultCode`
+    /// repeated forever.
+    ///
+    /// # Arguments
+    ///
+    /// * `effect` - The effect to measure.
+    fn kind_length_ms(&self, effect: EffectKind) -> Option<u64> {
+        let times = |count: u32, each: u64| u64::from(count).saturating_mul(each);
+        let valid = |ok: bool, length: u64| ok.then_some(length);
+        match effect {
+            EffectKind::Breath { duration } => Some(duration.into()),
+            EffectKind::Heartbeat {
+                flash_beats,
+                grouped_as,
+                bpm,
+            } => self.heartbeat_length_ms(flash_beats, grouped_as, bpm),
+            EffectKind::Staircase { levels, hold_ms } => {
+                valid(levels > 0, times(levels, hold_ms.into()))
+            }
+            EffectKind::Fade {
+                from,
+                to,
+                duration_ms,
+            } => valid(from <= LEVEL_MAX && to <= LEVEL_MAX, duration_ms.into()),
+            EffectKind::Blink {
+                on_ms,
+                off_ms,
+                times: count,
+            } => Some(times(count, u64::from(on_ms) + u64::from(off_ms))),
+            EffectKind::Throb {
+                attack_ms,
+                decay_ms,
+                cycles,
+            } => Some(times(cycles, u64::from(attack_ms) + u64::from(decay_ms))),
+            EffectKind::Cycle { period_ms, cycles } => Some(times(cycles, period_ms.into())),
+            EffectKind::Countdown { total_ms } => Some(total_ms.into()),
+            EffectKind::HeartbeatRealistic { bpm, beats } => {
+                Some(times(beats, 60_000u32.checked_div(bpm)?.into()))
+            }
+            EffectKind::LayeredBreath {
+                small_cycles,
+                small_low,
+                big_cycles,
+            } => {
+                let low = self.level_duty(small_low)?;
+                valid(
+                    low > self.pwm_min && low < self.pwm_max,
+                    times(small_cycles, LAYERED_SMALL_MS.into())
+                        .saturating_add(times(big_cycles, LAYERED_BIG_MS.into())),
+                )
+            }
+            EffectKind::Sparkle {
+                duration_ms,
+                density,
+                ..
+            } => valid(density <= u8::MAX.into(), duration_ms.into()),
+            EffectKind::OrganicBreath { duration_ms, .. } => Some(duration_ms.into()),
+            EffectKind::FaultCode { code, repeats } => {
+                let group = u64::from(code) * u64::from(FAULT_ON_MS + FAULT_OFF_MS)
+                    + u64::from(FAULT_PAUSE_MS);
+                valid((1..=u8::MAX.into()).contains(&code) && repeats > 0, times(repeats, group))
+            }
+            EffectKind::Distress { repeats } => {
+                let pattern: u32 = SOS_MS.iter().chain(SOS_GAP_MS.iter()).sum();
+                Some(times(repeats, pattern.into()))
+            }
+        }
+    }
+
+    /// Duty of an `EffectKind` brightness level, or `None` if it is out of range.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The level, spanning `0..=LEVEL_MAX` across `[pwm_min, pwm_max]`.
+    fn level_duty(&self, level: u32) -> Option<PWM::Duty> {
+        (level <= LEVEL_MAX).then(|| self.duty_from_fraction(level, LEVEL_MAX))
+    }
+
+    /// Level and length of the next slice of `sparkle`.
+    ///
+    /// # Arguments
+    ///
+    /// * `roll` - A fresh random number.
+    /// * `density` - The chance of a flash, out of 256.
+    /// * `remaining` - The time left of the effect, in milliseconds.
+    fn sparkle_slice(&self, roll: u32, density: u8, remaining: u32) -> (u32, u32) {
+        if roll & 0xFF < u32::from(density) {
+            let mid: u32 = Self::to_u32(self.pwm_mid);
+            let span = Self::to_u32(self.pwm_max) - mid;
+            (mid + (roll >> 8) % (span + 1), SPARKLE_SLICE_MS.min(remaining))
+        } else {
+            let dim = SPARKLE_SLICE_MS * (1 + (roll >> 8) % 4);
+            (Self::to_u32(self.pwm_min), dim.min(remaining))
+        }
+    }
+
+    /// Number of duty steps in a heartbeat decay lasting `decay_time` ms.
+    ///
+    /// One step per duty value from `pwm_mid` down to `pwm_min`, but at most
+    /// one per millisecond, so wide duty ranges do not take billions of writes.
+    ///
+    /// # Arguments
+    ///
+    /// * `decay_time` - The length of the decay in milliseconds.
+    #[inline(always)]
+    fn heartbeat_decay_steps(&self, decay_time: u32) -> u64 {
+        let span = u64::from(Self::to_u32(self.pwm_mid) - Self::to_u32(self.pwm_min)) + 1;
+        span.min(u64::from(decay_time)).max(1)
+    }
+
+    /// The duty written at step `step` of a heartbeat decay.
+    ///
+    /// Falls evenly from `pwm_mid` at step `0` to `pwm_min` at the last step,
+    /// one duty value per step when the range is narrow enough.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The step within the decay, in `0..steps`.
+    /// * `steps` - The number of steps, from `heartbeat_decay_steps`.
+    #[inline(always)]
+    fn heartbeat_decay_level(&self, step: u64, steps: u64) -> u32 {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let mid: u32 = Self::to_u32(self.pwm_mid);
+        if steps <= 1 {
+            return min;
+        }
+        mid - (u64::from(mid - min) * step / (steps - 1)) as u32
+    }
+
+    /// Apply the deadband to an input, returning the accepted input.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The new input on the `0..=65535` scale.
+    fn debounced(&mut self, input: u16) -> u16 {
+        match self.last_input {
+            Some(last) if input.abs_diff(last) <= self.deadband => last,
+            _ => {
+                self.last_input = Some(input);
+                input
+            }
+        }
+    }
+
+    /// Split a heartbeat at `bpm` into its base delays.
+    ///
+    /// `validate` guarantees `pwm_mid > pwm_min`, so only `bpm` can make a
+    /// division fail.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(u32, u32, u32)>` - The sixth of a beat, the flash length and
+    ///   the length of the decay, all in milliseconds, or `None` if `bpm` is
+    ///   zero.
+    #[inline(always)]
+    fn heartbeat_timing(&self, bpm: u32) -> Option<(u32, u32, u32)> {
+        let period_time = 60_000u32.checked_div(bpm)? / 6;
+        let short_period_time = period_time / 3;
+        Some((period_time, short_period_time, period_time * 2))
+    }
+
+    /// Convert the configured phase into a step offset within a cycle.
+    ///
+    /// # Arguments
+    ///
+    /// * `steps` - The number of steps in one cycle.
+    ///
+    /// # Returns
+    ///
+    /// * `u32` - The step at which the cycle should start, in `0..steps`.
+    #[inline(always)]
+    fn phase_shift(&self, steps: u32) -> u32 {
+        ((u64::from(self.phase) * u64::from(steps)) >> 16) as u32
+    }
+
+    /// Widen a duty value to the `u32` the effect math works on.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The duty value to convert.
+    #[inline(always)]
+    fn to_u32(duty: PWM::Duty) -> u32 {
+        duty.into()
+    }
+
+    /// Convert an effect value back to a duty, saturating at the duty type's maximum.
+    ///
+    /// A value too wide for a `u8` or `u16` duty becomes its maximum rather
+    /// than wrapping around to a dim level.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The duty value computed by an effect.
+    #[inline(always)]
+    fn from_u32(value: u32) -> PWM::Duty {
+        DutyValue::from_u32(value)
+    }
+
+    /// Clamp a raw duty value into `[pwm_min, pwm_max]`.
+    ///
+    /// Effects compute intermediate levels as plain `u32` values, where
+    /// saturating arithmetic only stops at `0`/`u32::MAX`. Clamping here
+    /// guarantees that no animated level can ever leave the configured range.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The raw duty value computed by an effect.
+    ///
+    /// # Returns
+    ///
+    /// * `PWM::Duty` - The value clamped into the configured range.
+    #[inline(always)]
+    fn clamp_duty(&self, value: u32) -> PWM::Duty {
+        Self::from_u32(value.clamp(Self::to_u32(self.pwm_min), Self::to_u32(self.pwm_max)))
+    }
+
+    /// Write an animated level to the pin, clamped into the configured range.
+    ///
+    /// Every brightness level produced by an effect goes through this method;
+    /// only the explicit "off" write bypasses it.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The raw duty value computed by an effect.
+    #[inline(always)]
+    fn write_duty(&mut self, value: u32) {
+        let duty = self.clamp_duty(value);
+        if self.jnd > 0 && self.perceived(duty).abs_diff(self.perceived(self.duty)) < self.jnd {
+            self.held = Some(duty);
+            self.pending = None;
+            return;
+        }
+        self.held = None;
+        if self.since_update_ms >= self.min_update_ms {
+            self.write_now(duty);
+        } else {
+            self.pending = Some(duty);
+        }
+    }
+
+    /// Write an animated level given in 1/256 duty steps.
+    ///
+    /// With dithering off the fraction is dropped. With it on, the fraction
+    /// is carried from one write to the next, so successive writes alternate
+    /// between adjacent duties and average out to the exact level.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The duty value computed by an effect, times 256.
+    fn write_fine(&mut self, value: u64) {
+        let value = if self.dither {
+            let carried = value + u64::from(self.dither_error);
+            self.dither_error = (carried & 0xFF) as u8;
+            carried >> 8
+        } else {
+            value >> 8
+        };
+        self.write_duty(value.min(u64::from(u32::MAX)) as u32);
+    }
+
+    /// Write `duty` to the pin immediately, dropping any coalesced update.
+    ///
+    /// Wakes the pin first if it was put to sleep. With `set_auto_disable`,
+    /// a dark duty disables the output after writing it, and nothing is
+    /// written to an output that is already disabled that way.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The duty value to write.
+    #[inline(always)]
+    fn write_now(&mut self, duty: PWM::Duty) {
+        self.wake();
+        self.pending = None;
+        self.held = None;
+        self.since_update_ms = 0;
+        let physical = self.output(duty);
+        let dark = self.auto_disable && physical == self.output(Self::from_u32(0));
+        if !dark {
+            self.resume();
+        }
+        if !self.disabled {
+            if self.max_slew > 0 {
+                self.slew_to(duty);
+            }
+            self.pin.set_duty(physical);
+            if self.verify && self.pin.get_duty() != physical {
+                self.fault = true;
+            }
+            if dark {
+                self.pin.disable();
+                self.disabled = true;
+            }
+        }
+        self.duty = duty;
+    }
+
+    /// Re-enable an output disabled by `set_auto_disable`.
+    ///
+    /// Does nothing if the output is not disabled that way.
+    fn resume(&mut self) {
+        if self.disabled {
+            self.pin.enable();
+            self.disabled = false;
+        }
+    }
+
+    /// Ramp the pin towards `duty` at the rate set with `set_max_slew`.
+    ///
+    /// Stops one step short of `duty`, which the caller then writes. Waits
+    /// one millisecond after each step, bypassing speed scaling and budgets.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The brightness duty about to be written.
+    fn slew_to(&mut self, duty: PWM::Duty) {
+        let target = Self::to_u32(duty);
+        let mut current = Self::to_u32(self.duty);
+        while current.abs_diff(target) > self.max_slew {
+            current = if current < target {
+                current + self.max_slew
+            } else {
+                current - self.max_slew
+            };
+            let physical = self.output(Self::from_u32(current));
+            self.pin.set_duty(physical);
+            self.spin_ms(1);
+        }
+    }
+
+    /// The value sent to the pin for a brightness duty.
+    ///
+    /// Applies the master brightness, the minimum on-time, the safe maximum
+    /// and inversion, and never exceeds the pin's `get_max_duty()`, even
+    /// with a `pwm_max` above it.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The brightness duty produced by an effect.
+    #[inline(always)]
+    fn output(&self, duty: PWM::Duty) -> PWM::Duty {
+        let mut duty = self.dimmed(duty);
+        if Self::to_u32(duty) != 0 {
+            duty = duty.max(self.min_on);
+        }
+        self.physical(duty.min(self.safe_max).min(self.pin.get_max_duty()))
+    }
+
+    /// Translate between a brightness duty and the value on the pin.
+    ///
+    /// The mapping is its own inverse: it mirrors the duty around the pin's
+    /// maximum when the output is inverted and is the identity otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The duty value to translate.
+    #[inline(always)]
+    fn physical(&self, duty: PWM::Duty) -> PWM::Duty {
+        if self.inverted {
+            let max = Self::to_u32(self.pin.get_max_duty());
+            Self::from_u32(max.saturating_sub(Self::to_u32(duty)))
+        } else {
+            duty
+        }
+    }
+
+    /// Apply the master brightness to the part of `duty` above `pwm_min`.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The brightness duty produced by an effect.
+    #[inline(always)]
+    fn dimmed(&self, duty: PWM::Duty) -> PWM::Duty {
+        if self.master == Percent::FULL || duty <= self.pwm_min {
+            return duty;
+        }
+        let min: u32 = Self::to_u32(self.pwm_min);
+        Self::from_u32(min + self.master.of(Self::to_u32(duty) - min))
+    }
+
+    /// The perceptual level of a brightness duty, on a 256-level gamma scale.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The brightness duty, relative to the pin's `get_max_duty()`.
+    fn perceived(&self, duty: PWM::Duty) -> u8 {
+        let max = u64::from(Self::to_u32(self.pin.get_max_duty())).max(1);
+        let linear = u64::from(Self::to_u32(duty)) * u64::from(u16::MAX) / max;
+        gamma::perceived(linear.min(u64::from(u16::MAX)) as u16)
+    }
+
+    /// Write out an update held back by `set_min_update_ms` or `set_jnd`, if any.
+    fn flush(&mut self) {
+        if let Some(duty) = self.pending.or(self.held) {
+            self.write_now(duty);
+        }
+    }
+
+    /// Move linearly from `from` to `to` over `duration_ms`, ending on `to`.
+    ///
+    /// Takes at most one step per millisecond and one step per duty value
+    /// (one per millisecond while dithering, one per frame with `set_fps`),
+    /// and at least one step, so a zero duration writes `to` immediately. Stops early when `stopped` is
+    /// set.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The duty value the ramp starts from (not written).
+    /// * `to` - The duty value the ramp ends on.
+    /// * `duration_ms` - The total time of the ramp in milliseconds.
+    fn ramp(&mut self, from: u32, to: u32, duration_ms: u32) {
+        let distance = from.abs_diff(to);
+        let steps = if let Some(frames) = self.frames_in(duration_ms) {
+            frames
+        } else if self.dither && distance > 0 {
+            duration_ms
+        } else {
+            distance.min(duration_ms)
+        }
+        .max(1);
+
+        let mut ramp = Ramp::new(from, to, steps);
+        ramp.next_fine(); // `from` is already on the pin
+
+        let mut elapsed = 0;
+        while let Some(value) = ramp.next_fine() {
+            self.write_fine(value);
+
+            let step = ramp.position() - 1;
+            let next = (u64::from(duration_ms) * step / u64::from(steps)) as u32;
+            self.delay_ms(next - elapsed);
+            elapsed = next;
+            if self.stopped() {
+                break;
+            }
+        }
+    }
+
+    /// Apply the configured `EndState` once an effect has finished.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - The outcome of the effect (see `complete`).
+    fn finish(&mut self) -> Result<(), Error> {
+        match self.end_state {
+            EndState::Off => self.write_now(self.off_duty),
+            EndState::Min => self.write_now(self.pwm_min),
+            EndState::Hold => self.flush(),
+        }
+        self.complete()
+    }
+
+    /// Report the outcome of an effect and reset the verify fault flag.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - `Error::Pwm` if a verified write did not take effect.
+    fn complete(&mut self) -> Result<(), Error> {
+        if core::mem::take(&mut self.fault) {
+            return Err(Error::Pwm);
+        }
+        let percent = self.brightness_percent();
+        if let Some(settle) = self.settle_hook.as_mut() {
+            settle(percent);
+        }
+        Ok(())
+    }
+
+    /// The current brightness as a rounded percentage of `[pwm_min, pwm_max]`.
+    ///
+    /// The inverse of the mapping in `set_progress`; duties at or below
+    /// `pwm_min` count as `0`.
+    fn brightness_percent(&self) -> u8 {
+        self.percent_of(self.current_duty())
+    }
+
+    /// `duty` as a rounded percentage of `[pwm_min, pwm_max]`, `0` at or below `pwm_min`.
+    ///
+    /// # Arguments
+    ///
+    /// * `duty` - The brightness duty to express as a percentage.
+    fn percent_of(&self, duty: PWM::Duty) -> u8 {
+        let min: u32 = Self::to_u32(self.pwm_min);
+        let range = u64::from(Self::to_u32(self.pwm_max) - min);
+        let above = u64::from(Self::to_u32(duty).saturating_sub(min));
+        ((above * 100 + range / 2) / range).min(100) as u8
+    }
+
+    /// Returns `true` when the running effect should stop early.
+    ///
+    /// That is the case once a `run_for` time budget has been used up, a
+    /// verified write has failed or the `Cancel` flag of a cancellable
+    /// effect has been raised. Effects check this after each delay.
+    #[inline(always)]
+    fn stopped(&self) -> bool {
+        self.fault
+            || self.budget_ms == Some(0)
+            || self.cancel.is_some_and(|cancel| cancel.is_cancelled())
+    }
+
+    /// Run `effect` so that raising `cancel` stops it after the current step.
+    ///
+    /// # Arguments
+    ///
+    /// * `cancel` - The flag to watch while the effect runs.
+    /// * `effect` - The effect to run.
+    fn cancellable<F>(&mut self, cancel: &Cancel, effect: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        /// Stops watching the flag when dropped, even if the effect panics.
+        struct Unwatch<'l, 'a, PWM: PwmPin, DELAY>(&'l mut LEDEffect<'a, PWM, DELAY>);
+
+        impl<PWM: PwmPin, DELAY> Drop for Unwatch<'_, '_, PWM, DELAY> {
+            fn drop(&mut self) {
+                self.0.cancel = None;
+            }
+        }
+
+        // SAFETY: `Unwatch` clears the flag before `cancel` is released.
+        self.cancel = Some(unsafe { Watched::new(cancel) });
+        let unwatch = Unwatch(self);
+        effect(unwatch.0)
+    }
+
+    /// Run `effect` with `end` in place of the configured `EndState`.
+    ///
+    /// # Arguments
+    ///
+    /// * `end` - The end state to apply for this run only.
+    /// * `effect` - The effect to run.
+    fn with_end<F>(&mut self, end: EndState, effect: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        let configured = core::mem::replace(&mut self.end_state, end);
+        let result = effect(self);
+        self.end_state = configured;
+        result
+    }
+
+    /// Delays execution for a specified number of milliseconds.
+    ///
+    /// The wait itself goes through the effect's `Delay` (a busy-wait
+    /// counting core cycles by default), unless a `set_yield` hook or a
+    /// software PWM pin takes it over. While a `run_for` time budget is
+    /// active the delay is truncated to what is left of it. An update held
+    /// back by `set_min_update_ms` is written as soon as its interval has
+    /// passed during the wait.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The number of milliseconds to delay execution.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// led_effect.delay_ms(500); // Delays for 500 milliseconds
+    /// ```
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: u32) {
+        let ms = self.scaled(ms);
+        let ms = self.jittered(ms);
+        let ms = match self.budget_ms.as_mut() {
+            Some(remaining) => {
+                let ms = ms.min(*remaining);
+                *remaining -= ms;
+                ms
+            }
+            None => ms,
+        };
+        self.wait_ms(ms);
+    }
+
+    /// Wait for exactly `ms` milliseconds, writing a coalesced update once
+    /// `set_min_update_ms` allows it.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The number of milliseconds to wait, already scaled.
+    fn wait_ms(&mut self, mut ms: u32) {
+        if let Some(duty) = self.pending {
+            let wait = self.min_update_ms.saturating_sub(self.since_update_ms).min(ms);
+            self.spin_ms(wait);
+            self.since_update_ms += wait;
+            ms -= wait;
+            if self.since_update_ms >= self.min_update_ms {
+                self.write_now(duty);
+            }
+        }
+
+        self.spin_ms(ms);
+        self.since_update_ms = self.since_update_ms.saturating_add(ms);
+    }
+
+    /// Scale a delay by the speed set with `set_speed`, carrying the remainder.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The delay requested by an effect, in milliseconds.
+    fn scaled(&mut self, ms: u32) -> u32 {
+        if self.speed == 100 {
+            return ms;
+        }
+        let total = u64::from(ms) * 100 + u64::from(self.speed_carry);
+        self.speed_carry = (total % u64::from(self.speed)) as u32;
+        (total / u64::from(self.speed)).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// Convert a duration at normal speed into one at the configured speed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The duration at normal speed, in milliseconds.
+    fn at_speed(&self, ms: u64) -> u32 {
+        (ms.saturating_mul(100) / u64::from(self.speed)).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// Apply the random variation configured with `set_jitter` to a delay.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The nominal delay in milliseconds; `0` is returned unchanged.
+    fn jittered(&mut self, ms: u32) -> u32 {
+        let Some((state, max_jitter_ms)) = self.jitter.as_mut() else {
+            return ms;
+        };
+        if ms == 0 {
+            return ms;
+        }
+
+        *state = xorshift32(*state);
+        let span = u64::from(*max_jitter_ms) * 2 + 1;
+        let offset = (u64::from(*state) % span) as u32;
+        if offset >= *max_jitter_ms {
+            ms.saturating_add(offset - *max_jitter_ms)
+        } else {
+            ms.saturating_sub(*max_jitter_ms - offset)
+        }
+    }
+
+    /// Busy-wait for `ms` milliseconds, kicking the watchdog hook on schedule.
+    ///
+    /// Without a watchdog hook this is a single busy-wait. With one, the wait
+    /// is split so that the hook runs every time its interval has elapsed,
+    /// counting time across consecutive delays.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The number of milliseconds to spin for.
+    fn spin_ms(&mut self, mut ms: u32) {
+        let Some(&(_, interval_ms)) = self.watchdog.as_ref() else {
+            self.spin_cycles_for(ms);
+            return;
+        };
+
+        while ms > 0 {
+            let chunk = ms.min(interval_ms - self.since_kick_ms);
+            self.spin_cycles_for(chunk);
+            self.since_kick_ms += chunk;
+            ms -= chunk;
+            if self.since_kick_ms >= interval_ms {
+                if let Some((kick, _)) = self.watchdog.as_mut() {
+                    kick();
+                }
+                self.since_kick_ms = 0;
+            }
+        }
+    }
+
+    /// Busy-wait for `ms` milliseconds using the configured clock frequency.
+    ///
+    /// A software PWM pin (see `new_soft`) is bit-banged for the whole wait
+    /// instead. Otherwise the wait is delegated to the `set_yield` hook, if
+    /// one is installed, or else to the effect's `Delay`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The number of milliseconds to spin for.
+    #[inline(always)]
+    fn spin_cycles_for(&mut self, ms: u32) {
+        if let Some(drive) = self.soft_drive {
+            let cycles_per_ms = self.clock_cycles_per_ms();
+            drive(&mut self.pin, ms, cycles_per_ms);
+            return;
+        }
+        if let Some(wait) = self.yield_hook.as_mut() {
+            if ms > 0 {
+                wait(ms);
+            }
+            return;
+        }
+        // Split long waits so that no single one overflows a 32-bit cycle count.
+        let cycles_per_ms = self.clock_cycles_per_ms();
+        let longest = (u32::MAX / cycles_per_ms.max(1)).max(1);
+        let mut ms = ms;
+        while ms > 0 {
+            let chunk = ms.min(longest);
+            self.delay.wait_ms(chunk, cycles_per_ms);
+            ms -= chunk;
+        }
+    }
+
+    /// Calculate the number of clock cycles per millisecond.
+    ///
+    /// This function returns the number of clock cycles that occur in one millisecond
+    /// based on the configured clock frequency (see `set_clock_hz` and `calibrate`).
+    /// For example, for a system running at 48MHz, it returns 48,000 cycles per
+    /// millisecond.
+    ///
+    /// # Returns
+    ///
+    /// * `u32` - The number of clock cycles in one millisecond.
+    #[inline(always)]
+    fn clock_cycles_per_ms(&self) -> u32 {
+        self.clock_hz / 1_000
+    }
+}
+
+impl<'a, P> LEDEffect<'a, SoftPwm<P>>
+where
+    P: OutputPin,
+{
+    /// Create an effect on a software PWM pin
+    ///
+    /// Like `new`, but every delay of the effects is spent toggling `pin`
+    /// at its configured frequency and duty (see the `soft` module), timed
+    /// by the core clock set with `set_clock_hz`. Any `set_yield` hook is
+    /// bypassed, since handing the wait to a scheduler would stop the PWM.
+    pub fn new_soft(pin: SoftPwm<P>, pwm_min: u16, pwm_max: u16) -> Result<Self, Error> {
+        let mut led = Self::new(pin, pwm_min, pwm_max)?;
+        led.soft_drive = Some(SoftPwm::drive);
+        Ok(led)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Создаем мок для тестирования
+    struct MockPwm {
+        duty: u32,
+        /// Whether the output is enabled
+        enabled: bool,
+        /// Ignore writes, like a wedged peripheral
+        stuck: bool,
+        /// Number of `set_duty` calls
+        writes: u32,
+        /// Number of writes equal to `get_max_duty()`
+        peaks: u32,
+        /// Lowest non-zero duty ever written (zero is the "off" write)
+        lowest: u32,
+        /// Highest duty ever written
+        highest: u32,
+        /// Sum of all duties written
+        total: u64,
+        /// Every duty written, with the mock delay time it was written at
+        timeline: std::vec::Vec<(u64, u32)>,
+        /// Value reported by `get_max_duty`
+        max: u32,
+    }
+
+    impl MockPwm {
+        /// Creates a new instance of `MockPwm` with the default duty cycle.
+        ///
+        /// This function initializes a `MockPwm` instance with a duty cycle set to `0`.
+        ///
+        /// # Returns
+        ///
+        /// * `Self` - A new `MockPwm` instance with the duty cycle initialized to `0`.
+        ///
+        /// ```
+        ///fn new() -> Self {
+        ///    Self { duty: 0 }
+        ///}
+        /// ```
+        fn new() -> Self {
+            Self {
+                duty: 0,
+                enabled: false,
+                stuck: false,
+                writes: 0,
+                peaks: 0,
+                lowest: u32::MAX,
+                highest: 0,
+                total: 0,
+                timeline: std::vec::Vec::new(),
+                max: 255,
+            }
+        }
+
+        /// Creates a `MockPwm` whose maximum duty is `max` instead of `255`.
+        fn with_max(max: u32) -> Self {
+            Self { max, ..Self::new() }
+        }
+    }
+
+    impl PwmPin for MockPwm {
+        type Duty = u32;
+
+        /// Disables the PWM output.
+        ///
+        /// This function disables the PWM output and stops updating the duty cycle.
+        /// After calling this function, the `duty` field is no longer updated.
+        /// ```
+        ///#[inline(always)]
+        ///fn disable(&mut self) {}
+        /// ```
+        fn disable(&mut self) {
+            self.enabled = false;
+        }
+        /// Enables the PWM output.
+        ///
+        /// This function enables the PWM output and starts updating the duty cycle
+        /// based on the value of the `duty` field.
+        ///
+        fn enable(&mut self) {
+            self.enabled = true;
+        }
+        /// Returns the current duty cycle of the PWM pin.
+        ///
+        /// This function retrieves the current duty cycle value of the PWM pin.
+        /// The duty cycle is a measure of the proportion of 'on' time to the
+        /// regular interval or 'period' of the PWM signal. A higher duty cycle
+        /// corresponds to a brighter LED when used in LED applications.
+        ///
+        /// # Returns
+        ///
+        /// * `Self::Duty` - The current duty cycle of the PWM pin.
+        ///
+        fn get_duty(&self) -> Self::Duty {
+            self.duty
+        }
+        /// Returns the maximum possible duty cycle value of the PWM pin.
+        ///
+        /// # Returns
+        ///
+        /// * `Self::Duty` - The maximum possible duty cycle value of the PWM pin.
+        ///
+        fn get_max_duty(&self) -> Self::Duty {
+            self.max
+        }
+        /// Sets the duty cycle of the PWM pin.
+        ///
+        /// This function sets the duty cycle of the PWM pin to the given value.
+        /// The duty cycle is a measure of the proportion of 'on' time to the
+        /// regular interval or 'period' of the PWM signal. A higher duty cycle
+        /// corresponds to a brighter LED when used in LED applications.
+        ///
+        /// # Arguments
+        ///
+        /// * `duty` - The new duty cycle value of the PWM pin.
+        ///
+        fn set_duty(&mut self, duty: Self::Duty) {
+            if self.stuck {
+                return;
+            }
+            self.duty = duty;
+            self.writes += 1;
+            if duty == self.get_max_duty() {
+                self.peaks += 1;
+            }
+            if duty != 0 {
+                self.lowest = self.lowest.min(duty);
+            }
+            self.highest = self.highest.max(duty);
+            self.total += u64::from(duty);
+            self.timeline.push((REQUESTED_MS.with(|total| total.get()), duty));
+        }
+    }
+
+    extern crate std;
+
+    std::thread_local! {
+        /// Milliseconds of delay requested on the current test thread
+        static REQUESTED_MS: core::cell::Cell<u64> = const { core::cell::Cell::new(0) };
+    }
+
+    /// Mock delay for `set_yield` that accumulates the requested time.
+    ///
+    /// The total is kept per thread, so tests running in parallel do not
+    /// see each other's delays.
+    fn accumulate_delay(ms: u32) {
+        REQUESTED_MS.with(|total| total.set(total.get() + u64::from(ms)));
+    }
+
+    /// `accumulate_delay` as a hook that can be installed on any effect.
+    fn accumulating() -> &'static mut (dyn FnMut(u32) + Send) {
+        // A function item takes no space, so leaking it allocates nothing.
+        std::boxed::Box::leak(std::boxed::Box::new(accumulate_delay))
+    }
+
+    /// Run `effect` on `led` with the mock delay installed and return the
+    /// total delay it requested, in milliseconds.
+    fn requested_ms<F>(led: &mut LEDEffect<'_, MockPwm>, effect: F) -> u64
+    where
+        F: FnOnce(&mut LEDEffect<'_, MockPwm>) -> Result<(), Error>,
+    {
+        led.set_yield(accumulating());
+        REQUESTED_MS.with(|total| total.set(0));
+        effect(led).unwrap();
+        led.clear_yield();
+        REQUESTED_MS.with(|total| total.get())
+    }
+
+    /// Deterministic linear congruential generator for effects that take an RNG.
+    struct TestRng(u32);
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            self.0
+        }
+        fn next_u64(&mut self) -> u64 {
+            u64::from(self.next_u32())
+        }
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    /// Tests creating a new instance of the `LEDEffect` struct.
+    ///
+    /// This test creates a new instance of the `LEDEffect` struct with a valid
+    /// set of parameters. The test asserts that the `LEDEffect` instance is created
+    /// successfully.
+    #[test]
+    fn test_new_led_effect() {
+        let pin = MockPwm::new();
+        let led = LEDEffect::new(pin, 5, 255);
+        assert!(led.is_ok());
+    }
+
+    /// Tests that creating a new `LEDEffect` instance with invalid parameters fails.
+    ///
+    /// This test creates a new instance of the `LEDEffect` struct with an invalid
+    /// set of parameters (i.e. `pwm_min` >= `pwm_max`). The test asserts that the
+    /// `LEDEffect` instance cannot be created and that the error variant is
+    /// `Error::InvalidParameter`.
+    ///
+    #[test]
+    fn test_invalid_parameters() {
+        let pin = MockPwm::new();
+        let led = LEDEffect::new(pin, 255, 5);
+        assert!(matches!(led, Err(Error::InvalidParameter)));
+    }
+
+    /// Tests that `on` and `off` write the full and zero duty cycles.
+    #[test]
+    fn test_on_off() {
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 200).unwrap();
+        led.on().unwrap();
+        assert_eq!(led.pin.get_duty(), 200);
+        led.off().unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+    }
+
+    /// Tests that the clock frequency defaults to the build-time clock and
+    /// rejects values too low to express a millisecond in cycles.
+    #[test]
+    fn test_clock_hz() {
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 200).unwrap();
+        assert_eq!(led.clock_hz(), DEFAULT_CLOCK_HZ);
+        assert_eq!(led.clock_cycles_per_ms(), DEFAULT_CLOCK_HZ / 1_000);
+        #[cfg(not(any(feature = "clk-72mhz", feature = "clk-125mhz", feature = "clk-168mhz")))]
+        assert_eq!(led.clock_cycles_per_ms(), 48_000);
+        #[cfg(feature = "clk-168mhz")]
+        assert_eq!(led.clock_cycles_per_ms(), 168_000);
+
+        assert!(led.set_clock_hz(72_000_000).is_ok());
+        assert_eq!(led.clock_cycles_per_ms(), 72_000);
+
+        assert!(matches!(led.set_clock_hz(999), Err(Error::InvalidParameter)));
+        assert_eq!(led.clock_hz(), 72_000_000);
+    }
+
+    /// Tests that `clamp_duty` pins raw values to the configured range.
+    #[test]
+    fn test_clamp_duty() {
+        let led = LEDEffect::new(MockPwm::new(), 10, 200).unwrap();
+        assert_eq!(led.clamp_duty(0), 10);
+        assert_eq!(led.clamp_duty(100), 100);
+        assert_eq!(led.clamp_duty(u32::MAX), 200);
+    }
+
+    /// Tests that no effect writes a duty outside `[pwm_min, pwm_max]`.
+    ///
+    /// The effects are driven with adversarial inputs (a fade starting from a
+    /// pin that reports an out-of-range duty, a saturated sparkle density, a
+    /// `pwm_min` of zero). Apart from the final "off" write of `0`, every duty
+    /// the mock observes must stay within the configured range.
+    #[test]
+    fn test_effects_stay_in_range() {
+        let mut pin = MockPwm::new();
+        pin.duty = 255;
+        let mut led = LEDEffect::new(pin, 20, 120).unwrap();
+        led.fade(20, 50).unwrap();
+        led.breath(600).unwrap();
+        led.heartbeat(2, 2, 120).unwrap();
+        led.sparkle(&mut TestRng(7), 500, 255).unwrap();
+        let pin = led.destroy();
+        assert!(pin.lowest >= 20);
+        assert!(pin.highest <= 120);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 60).unwrap();
+        led.heartbeat(1, 1, 60).unwrap();
+        assert!(led.destroy().highest <= 60);
+    }
+
+    /// Tests that the staircase visits both ends of the range and rejects zero levels.
+    #[test]
+    fn test_staircase() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        assert!(matches!(led.staircase(0, 10), Err(Error::InvalidParameter)));
+
+        led.set_end_state(EndState::Hold);
+        led.staircase(5, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 110);
+        let pin = led.destroy();
+        assert_eq!(pin.lowest, 10);
+        assert_eq!(pin.highest, 110);
+    }
+
+    /// Tests that `run_for` repeats an effect and truncates the last repetition.
+    #[test]
+    fn test_run_for() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 40).unwrap();
+        led.set_end_state(EndState::Hold);
+
+        let stairs = EffectKind::Staircase { levels: 4, hold_ms: 100 };
+        led.run_for(stairs, 250).unwrap();
+        assert_eq!(led.pin.get_duty(), 30);
+
+        let stairs = EffectKind::Staircase { levels: 2, hold_ms: 100 };
+        led.run_for(stairs, 450).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+
+        // An effect that takes no time must not loop forever.
+        let instant = EffectKind::Staircase { levels: 3, hold_ms: 0 };
+        led.run_for(instant, 100).unwrap();
+        assert_eq!(led.pin.get_duty(), 40);
+        assert_eq!(led.budget_ms, None);
+    }
+
+    /// Tests that a minimum update interval coalesces writes but keeps the endpoint.
+    #[test]
+    fn test_min_update_interval() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 100).unwrap();
+        led.fade(100, 100).unwrap();
+        assert_eq!(led.pin.writes, 100);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 100).unwrap();
+        led.set_min_update_ms(10);
+        led.fade(100, 100).unwrap();
+        assert_eq!(led.pin.get_duty(), 100);
+        assert!(led.pin.writes <= 11, "{} writes", led.pin.writes);
+    }
+
+    /// Tests that verify mode reports a wedged pin as `Error::Pwm`.
+    #[test]
+    fn test_verify_mode() {
+        let mut pin = MockPwm::new();
+        pin.stuck = true;
+        let mut led = LEDEffect::new(pin, 10, 100).unwrap();
+        assert!(led.on().is_ok());
+        assert!(led.breath(600).is_ok());
+
+        led.set_verify(true);
+        assert!(matches!(led.on(), Err(Error::Pwm)));
+        assert!(matches!(led.breath(600), Err(Error::Pwm)));
+        assert!(matches!(led.fade(50, 100), Err(Error::Pwm)));
+
+        led.pin.stuck = false;
+        assert!(led.on().is_ok());
+        assert!(led.breath(600).is_ok());
+    }
+
+    /// Tests that `set_level` maps perceptual levels onto the duty range.
+    #[test]
+    fn test_set_level() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        led.set_level(0).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        led.set_level(u16::MAX).unwrap();
+        assert_eq!(led.pin.get_duty(), 210);
+        led.set_level(32768).unwrap();
+        let half = led.pin.get_duty();
+        assert!(half > 10 && half < 110, "{}", half);
+    }
+
+    /// Tests that inverted output mirrors writes and reads around the max duty.
+    #[test]
+    fn test_inverted_output() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 200).unwrap();
+        led.set_inverted(true);
+        led.on().unwrap();
+        assert_eq!(led.pin.get_duty(), 55);
+        led.off().unwrap();
+        assert_eq!(led.pin.get_duty(), 255);
+        led.set_level(u16::MAX).unwrap();
+        assert_eq!(led.pin.get_duty(), 55);
+
+        led.off().unwrap();
+        led.set_end_state(EndState::Hold);
+        led.fade(100, 50).unwrap();
+        assert_eq!(led.pin.get_duty(), 155);
+    }
+
+    /// Tests driving a breathing cycle and a fade one step at a time.
+    #[test]
+    fn test_advance() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 50).unwrap();
+        assert!(!led.advance());
+        assert!(matches!(led.begin_breath(0), Err(Error::InvalidParameter)));
+
+        led.begin_breath(5).unwrap();
+        let mut seen = [0u32; 5];
+        for (i, duty) in seen.iter_mut().enumerate() {
+            let running = led.advance();
+            *duty = led.pin.get_duty();
+            assert_eq!(running, i < 4);
+        }
+        assert_eq!(seen[..4], [10, 30, 50, 30]);
+        assert_eq!(led.pin.get_duty(), 0);
+        assert!(!led.advance());
+
+        led.begin_fade(50, 4).unwrap();
+        while led.advance() {}
+        assert_eq!(led.pin.get_duty(), 50);
+        assert_eq!(led.pin.writes, 10);
+    }
+
+    /// Tests that the master brightness scales the offset above `pwm_min`.
+    #[test]
+    fn test_master_brightness() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        assert_eq!(led.master(), Percent::FULL);
+        led.set_master(Percent::ZERO);
+        led.on().unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+
+        led.set_master(Percent::from_literal(20));
+        led.on().unwrap();
+        assert_eq!(led.pin.get_duty(), 50);
+        led.off().unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+
+        // A fade continues from the undimmed level rather than the pin value,
+        // so fading to the level that is already lit takes a single write.
+        led.on().unwrap();
+        led.fade(210, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 50);
+        assert_eq!(led.pin.writes, 5);
+    }
+
+    /// Tests that `follow` maps readings onto the range and honours the slew limit.
+    #[test]
+    fn test_follow() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.follow(|| u16::MAX, 1, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 110);
+        led.follow(|| 0, 1, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+
+        led.set_follow_slew(20);
+        let mut readings = [u16::MAX, u16::MAX, 0].into_iter();
+        led.follow(|| readings.next().unwrap(), 2, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 50);
+        led.follow(|| readings.next().unwrap(), 1, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 30);
+    }
+
+    /// Tests that an envelope reaches its clamped peak and tolerates empty phases.
+    #[test]
+    fn test_play_envelope() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        let env = Envelope {
+            attack_ms: 20,
+            hold_ms: 50,
+            release_ms: 40,
+            peak: 80,
+        };
+        led.play_envelope(&env).unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+        assert_eq!(led.pin.highest, 80);
+
+        let env = Envelope {
+            attack_ms: 0,
+            hold_ms: 0,
+            release_ms: 0,
+            peak: 250,
+        };
+        led.set_end_state(EndState::Hold);
+        led.play_envelope(&env).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        assert_eq!(led.pin.highest, 100);
+    }
+
+    /// Tests the hard and crossfaded wraps of the cyclic effect.
+    #[test]
+    fn test_cycle() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 10).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.cycle(100, 2).unwrap();
+        // Two cycles of one write at min plus ten ramp steps each.
+        assert_eq!(led.pin.writes, 22);
+        assert_eq!(led.pin.get_duty(), 10);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 10).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.set_cycle_crossfade_ms(20);
+        led.cycle(100, 2).unwrap();
+        // A single write at min, then ten rising and ten falling steps per cycle.
+        assert_eq!(led.pin.writes, 41);
+        assert_eq!(led.pin.get_duty(), 0);
+    }
+
+    /// Tests that hooks leave the effect `Send`, so it can be an RTIC resource.
+    #[test]
+    fn test_effect_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<LEDEffect<'static, MockPwm>>();
+    }
+
+    /// Tests that the watchdog hook runs on schedule across consecutive delays.
+    #[test]
+    fn test_watchdog_hook() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        let kicks = AtomicU32::new(0);
+        let mut kick = || {
+            kicks.fetch_add(1, Ordering::Relaxed);
+        };
+        let mut unused = || {};
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        assert!(matches!(led.set_watchdog(&mut unused, 0), Err(Error::InvalidParameter)));
+
+        led.set_watchdog(&mut kick, 100).unwrap();
+        led.delay_ms(250);
+        assert_eq!(kicks.load(Ordering::Relaxed), 2);
+        led.delay_ms(50);
+        assert_eq!(kicks.load(Ordering::Relaxed), 3);
+        led.fade(100, 90).unwrap();
+        assert_eq!(kicks.load(Ordering::Relaxed), 3);
+        led.fade(10, 10).unwrap();
+        assert_eq!(kicks.load(Ordering::Relaxed), 4);
+
+        led.clear_watchdog();
+        led.delay_ms(1000);
+        assert_eq!(kicks.load(Ordering::Relaxed), 4);
+    }
+
+    /// Tests that `fade_between` starts at the clamped `from` in either direction.
+    #[test]
+    fn test_fade_between() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        led.fade_between(200, 40, 60).unwrap();
+        assert_eq!(led.pin.highest, 100);
+        assert_eq!(led.pin.get_duty(), 40);
+
+        led.fade_between(0, 60, 50).unwrap();
+        assert_eq!(led.pin.lowest, 10);
+        assert_eq!(led.pin.get_duty(), 60);
+    }
+
+    /// Tests the breathing shape and that phases shift where the cycle starts.
+    #[test]
+    fn test_breath_phase() {
+        assert_eq!(breath_offset(90, 0, 30), 0);
+        assert_eq!(breath_offset(90, 15, 30), 45 << 8);
+        assert_eq!(breath_offset(90, 30, 30), 90 << 8);
+        assert_eq!(breath_offset(90, 45, 30), 45 << 8);
+        assert_eq!(breath_offset(90, 60, 30), 0);
+        assert_eq!(breath_offset(90, 89, 30), 0);
+        assert_eq!(breath_offset(3, 1, 2), 3 << 7);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 90).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.breath(3).unwrap();
+        assert_eq!(led.pin.writes, 3);
+        assert_eq!(led.pin.get_duty(), 0);
+        assert_eq!(led.pin.highest, 90);
+
+        // Half a cycle in, the first step lands on the peak.
+        led.set_phase(32768);
+        led.begin_breath(4).unwrap();
+        led.advance();
+        assert_eq!(led.pin.get_duty(), 90);
+    }
+
+    /// Tests the rounding and clamping of `duty_from_fraction`.
+    #[test]
+    fn test_duty_from_fraction() {
+        let led = LEDEffect::new(MockPwm::new(), 0, 65535).unwrap();
+        let half = led.duty_from_fraction(50, 100);
+        assert!(half.abs_diff(32767) <= 1, "{}", half);
+        assert_eq!(led.duty_from_fraction(100, 100), 65535);
+        assert_eq!(led.duty_from_fraction(u32::MAX, u32::MAX), 65535);
+        assert_eq!(led.duty_from_fraction(0, 100), 0);
+
+        let led = LEDEffect::new(MockPwm::new(), 10, 13).unwrap();
+        assert_eq!(led.duty_from_fraction(1, 3), 11);
+        assert_eq!(led.duty_from_fraction(1, 2), 12);
+        assert_eq!(led.duty_from_fraction(2, 3), 12);
+        assert_eq!(led.duty_from_fraction(3, 3), 13);
+        assert_eq!(led.duty_from_fraction(7, 3), 13);
+        assert_eq!(led.duty_from_fraction(1, 0), 10);
+    }
+
+    /// Tests that organic breathing stays in range and varies between cycles.
+    #[test]
+    fn test_organic_breath() {
+        let mut led = LEDEffect::new(MockPwm::with_max(1010), 10, 1010).unwrap();
+        led.organic_breath(&mut TestRng(3), 12_000).unwrap();
+        let pin = led.destroy();
+        assert!(pin.lowest >= 10);
+        assert!(pin.highest <= 1010);
+        assert!(pin.highest >= 760, "{}", pin.highest);
+        // Three nominal four-second cycles at 20 ms per frame.
+        assert!((550..=650).contains(&pin.writes), "{}", pin.writes);
+    }
+
+    /// Tests that static samples are mapped onto the duty range in order.
+    #[test]
+    fn test_play_static() {
+        static TABLE: [u16; 3] = [0, 32768, u16::MAX];
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.play_static(&TABLE, 10).unwrap();
+        let pin = led.destroy();
+        assert_eq!(pin.writes, 3);
+        assert_eq!(pin.lowest, 10);
+        assert_eq!(pin.duty, 110);
+    }
+
+    /// Tests that `pwm_max` is written exactly once per cycle when it equals
+    /// the pin's maximum duty.
+    ///
+    /// Regression test for ramps whose top boundary relied on saturating
+    /// arithmetic and could skip the maximum or loop one step short.
+    #[test]
+    fn test_max_duty_boundary() {
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 255).unwrap();
+        led.breath(3000).unwrap();
+        assert_eq!(led.pin.peaks, 1);
+
+        for total in 1..=8 {
+            let mut led = LEDEffect::new(MockPwm::new(), 5, 255).unwrap();
+            led.begin_breath(total).unwrap();
+            while led.advance() {}
+            assert_eq!(led.pin.peaks, 1, "{} steps", total);
+        }
+
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 255).unwrap();
+        led.heartbeat(3, 3, 60).unwrap();
+        led.staircase(4, 10).unwrap();
+        led.cycle(500, 2).unwrap();
+        assert_eq!(led.pin.peaks, 3 + 1 + 2);
+    }
+
+    /// Tests pre-flight validation of candidate configurations.
+    #[test]
+    fn test_validate() {
+        type Led = LEDEffect<'static, MockPwm>;
+        assert!(Led::validate(5, 255, DEFAULT_CLOCK_HZ).is_ok());
+        assert!(Led::validate(0, 2, MIN_CLOCK_HZ).is_ok());
+        assert!(matches!(Led::validate(255, 5, DEFAULT_CLOCK_HZ), Err(Error::InvalidParameter)));
+        assert!(matches!(Led::validate(5, 5, DEFAULT_CLOCK_HZ), Err(Error::InvalidParameter)));
+        assert!(matches!(Led::validate(5, 6, DEFAULT_CLOCK_HZ), Err(Error::InvalidParameter)));
+        assert!(matches!(Led::validate(5, 255, 0), Err(Error::InvalidParameter)));
+        assert!(matches!(Led::validate(5, 255, 999), Err(Error::InvalidParameter)));
+
+        assert!(matches!(
+            LEDEffect::new(MockPwm::new(), 5, 6),
+            Err(Error::InvalidParameter)
+        ));
+    }
+
+    /// Tests that the realistic heartbeat flashes to full once per beat.
+    #[test]
+    fn test_heartbeat_realistic() {
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 255).unwrap();
+        assert!(matches!(led.heartbeat_realistic(0, 1), Err(Error::InvalidParameter)));
+
+        led.heartbeat_realistic(60, 3).unwrap();
+        assert_eq!(led.pin.peaks, 3);
+        assert_eq!(led.pin.lowest, 5);
+        assert_eq!(led.pin.get_duty(), 0);
+    }
+
+    /// Tests that effects can run on a borrowed pin that stays usable afterwards.
+    #[test]
+    fn test_borrowed_pin() {
+        let mut pin = MockPwm::new();
+        borrowed::fade_on(&mut pin, 10, 100, 60, 50).unwrap();
+        assert_eq!(pin.get_duty(), 60);
+
+        pin.set_duty(77);
+        borrowed::breath_on(&mut pin, 10, 100, 300).unwrap();
+        assert_eq!(pin.get_duty(), 0);
+        assert_eq!(pin.highest, 100);
+
+        {
+            let mut led = LEDEffect::new(PinRef::new(&mut pin), 10, 100).unwrap();
+            led.on().unwrap();
+        }
+        assert_eq!(pin.get_duty(), 100);
+    }
+
+    /// Tests that the trough hold lengthens `breath` by dwelling at `pwm_min`.
+    #[test]
+    fn test_trough_hold() {
+        let breath = EffectKind::Breath { duration: 300 };
+
+        // Without a dwell the first cycle ends after 300 ms and the second
+        // one is 20 steps into its rise when the budget runs out.
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.run_for(breath, 320).unwrap();
+        assert_eq!(led.pin.get_duty(), 29);
+
+        // With a 50 ms dwell the first cycle is still resting at the trough.
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.set_trough_hold_ms(50);
+        led.run_for(breath, 320).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+    }
+
+    /// Tests that the predicted durations match the time the effects spend waiting.
+    ///
+    /// A watchdog hook kicked every millisecond counts the elapsed time.
+    #[test]
+    fn test_duration_prediction() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        let elapsed_ms = AtomicU32::new(0);
+        let mut tick = || {
+            elapsed_ms.fetch_add(1, Ordering::Relaxed);
+        };
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_watchdog(&mut tick, 1).unwrap();
+
+        let predicted = led.heartbeat_duration_ms(5, 2, 72);
+        led.heartbeat(5, 2, 72).unwrap();
+        assert_eq!(elapsed_ms.swap(0, Ordering::Relaxed), predicted);
+        assert_ne!(predicted % (60_000 / 72), 0);
+
+        led.set_trough_hold_ms(40);
+        let predicted = led.breath_duration_ms(1_000);
+        led.breath(1_000).unwrap();
+        assert_eq!(elapsed_ms.swap(0, Ordering::Relaxed), predicted);
+        assert_eq!(predicted, 1_040);
+    }
+
+    /// Tests that an installed yield hook receives every wait.
+    #[test]
+    fn test_yield_hook() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        let yielded_ms = AtomicU32::new(0);
+        let calls = AtomicU32::new(0);
+        let mut wait = |ms| {
+            yielded_ms.fetch_add(ms, Ordering::Relaxed);
+            calls.fetch_add(1, Ordering::Relaxed);
+        };
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        led.set_yield(&mut wait);
+        led.delay_ms(0);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+        led.breath(600).unwrap();
+        assert_eq!(yielded_ms.load(Ordering::Relaxed), 600);
+
+        led.clear_yield();
+        led.delay_ms(100);
+        assert_eq!(yielded_ms.load(Ordering::Relaxed), 600);
+    }
+
+    /// Tests that the safe maximum caps every write without rescaling effects.
+    #[test]
+    fn test_safe_max() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 200).unwrap();
+        assert_eq!(led.safe_max(), 200);
+        assert!(matches!(led.set_safe_max(5), Err(Error::InvalidParameter)));
+        assert!(matches!(led.set_safe_max(201), Err(Error::InvalidParameter)));
+
+        led.set_safe_max(150).unwrap();
+        led.on().unwrap();
+        assert_eq!(led.pin.get_duty(), 150);
+        led.set_level(u16::MAX).unwrap();
+        assert_eq!(led.pin.get_duty(), 150);
+
+        led.breath(600).unwrap();
+        led.fade(100, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 100);
+        let pin = led.destroy();
+        assert_eq!(pin.highest, 150);
+        assert_eq!(pin.lowest, 10);
+    }
+
+    /// Tests that `transition_to` starts from the current level and clamps its target.
+    #[test]
+    fn test_transition_to() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.staircase(2, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 110);
+
+        led.transition_to(60, 50).unwrap();
+        assert_eq!(led.pin.get_duty(), 60);
+        assert_eq!(led.pin.lowest, 10);
+
+        // From off the transition rises from `pwm_min`, never writing below it.
+        led.off().unwrap();
+        led.transition_to(255, 20).unwrap();
+        assert_eq!(led.pin.get_duty(), 110);
+        let pin = led.destroy();
+        assert_eq!(pin.lowest, 10);
+        assert_eq!(pin.highest, 110);
+    }
+
+    /// Tests that raw duty writes are checked against the pin's maximum only.
+    #[test]
+    fn test_set_duty_raw() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_inverted(true);
+        led.set_safe_max(50).unwrap();
+
+        led.set_duty_raw(200).unwrap();
+        assert_eq!(led.pin.get_duty(), 200);
+        led.set_duty_raw(0).unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+
+        assert!(matches!(led.set_duty_raw(256), Err(Error::InvalidParameter)));
+        assert_eq!(led.pin.get_duty(), 0);
+        assert_eq!(led.pin.writes, 2);
+    }
+
+    /// Tests that progress maps linearly onto the range and clamps above 100%.
+    #[test]
+    fn test_set_progress() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        led.set_progress(0, 0).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+
+        led.set_progress(25, 100).unwrap();
+        assert_eq!(led.pin.get_duty(), 60);
+        let writes = led.pin.writes;
+        led.set_progress(50, 100).unwrap();
+        assert_eq!(led.pin.get_duty(), 110);
+        assert_eq!(led.pin.writes - writes, 50);
+
+        led.set_progress(150, 10).unwrap();
+        assert_eq!(led.pin.get_duty(), 210);
+    }
+
+    /// Tests that jitter varies delays within its bound and never underflows.
+    #[test]
+    fn test_jitter() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        let elapsed_ms = AtomicU32::new(0);
+        let mut wait = |ms| {
+            elapsed_ms.fetch_add(ms, Ordering::Relaxed);
+        };
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        led.set_yield(&mut wait);
+        led.set_jitter(&mut TestRng(3), 10);
+
+        let mut distinct = false;
+        for _ in 0..50 {
+            led.delay_ms(100);
+            let elapsed = elapsed_ms.swap(0, Ordering::Relaxed);
+            assert!((90..=110).contains(&elapsed));
+            distinct |= elapsed != 100;
+        }
+        assert!(distinct);
+
+        led.delay_ms(3);
+        assert!(elapsed_ms.swap(0, Ordering::Relaxed) <= 13);
+        led.delay_ms(0);
+        assert_eq!(elapsed_ms.load(Ordering::Relaxed), 0);
+
+        led.clear_jitter();
+        led.delay_ms(100);
+        assert_eq!(elapsed_ms.load(Ordering::Relaxed), 100);
+    }
+
+    /// Tests that `sleep` fades out and disables the pin until the next write.
+    #[test]
+    fn test_sleep_wake() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        assert!(led.pin.enabled);
+        led.on().unwrap();
+
+        led.sleep(100).unwrap();
+        assert!(led.is_asleep());
+        assert!(!led.pin.enabled);
+        assert_eq!(led.pin.get_duty(), 0);
+        assert_eq!(led.pin.lowest, 10);
+
+        led.wake();
+        assert!(!led.is_asleep());
+        assert!(led.pin.enabled);
+
+        // Effects wake a sleeping pin on their own.
+        led.sleep(0).unwrap();
+        led.breath(300).unwrap();
+        assert!(!led.is_asleep());
+        assert!(led.pin.enabled);
+    }
+
+    /// Tests that a dark LED disables the output until it lights up again.
+    #[test]
+    fn test_auto_disable() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_auto_disable(true);
+        assert!(led.auto_disable());
+        led.on().unwrap();
+        assert!(led.pin.enabled);
+
+        led.off().unwrap();
+        assert!(!led.pin.enabled);
+        assert_eq!(led.pin.get_duty(), 0);
+        let writes = led.pin.writes;
+        led.off().unwrap();
+        led.breath(300).unwrap();
+        assert!(!led.pin.enabled);
+        assert!(led.pin.writes > writes);
+        assert_eq!(led.pin.get_duty(), 0);
+
+        // Inverted, dark is the pin's maximum.
+        led.set_inverted(true);
+        led.on().unwrap();
+        assert!(led.pin.enabled);
+        led.off().unwrap();
+        assert!(!led.pin.enabled);
+        assert_eq!(led.pin.get_duty(), 255);
+
+        // Sleeping and raw writes keep the bookkeeping straight.
+        led.sleep(0).unwrap();
+        led.wake();
+        assert!(led.pin.enabled);
+        led.off().unwrap();
+        led.set_duty_raw(40).unwrap();
+        assert!(led.pin.enabled);
+        led.off().unwrap();
+        led.set_auto_disable(false);
+        assert!(led.pin.enabled);
+        led.off().unwrap();
+        assert!(led.pin.enabled);
+    }
+
+    /// Tests that `throb` steps its attack and decay independently.
+    #[test]
+    fn test_throb() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_end_state(EndState::Hold);
+        led.throb(20, 200, 3).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        // 20 attack steps plus 100 decay steps (one per duty value) per cycle.
+        assert_eq!(led.pin.writes, 3 * (20 + 100));
+        let pin = led.destroy();
+        assert_eq!(pin.lowest, 10);
+        assert_eq!(pin.highest, 110);
+    }
+
+    /// Tests that a configuration survives a round trip and is validated.
+    #[test]
+    fn test_config_round_trip() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 200).unwrap();
+        led.set_clock_hz(72_000_000).unwrap();
+        led.set_inverted(true);
+        led.set_gamma(false);
+        let heartbeat = EffectKind::Heartbeat { flash_beats: 3, grouped_as: 3, bpm: 60 };
+        led.set_default_effect(Some(heartbeat));
+
+        let config = led.to_config();
+        assert_eq!(config.effect, config::EFFECT_HEARTBEAT);
+        assert_eq!(config.params, [3, 3, 60]);
+
+        let led = LEDEffect::from_config(MockPwm::new(), &config).unwrap();
+        assert_eq!(led.to_config(), config);
+        assert_eq!(led.clock_hz(), 72_000_000);
+        assert!(led.inverted());
+        assert!(!led.gamma());
+        assert_eq!(led.default_effect(), Some(heartbeat));
+
+        let bad_effect = Config { effect: 99, ..config };
+        assert!(matches!(
+            LEDEffect::from_config(MockPwm::new(), &bad_effect),
+            Err(Error::InvalidParameter)
+        ));
+        let bad_range = Config { pwm_min: 200, pwm_max: 10, ..config };
+        assert!(matches!(
+            LEDEffect::from_config(MockPwm::new(), &bad_range),
+            Err(Error::InvalidParameter)
+        ));
+    }
+
+    /// Tests that effects request exactly the delays their parameters describe.
+    #[test]
+    fn test_effect_timing() {
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 255).unwrap();
+
+        assert_eq!(requested_ms(&mut led, |led| led.breath(6_000)), 6_000);
+        // Fewer duty steps than milliseconds: still exactly the duration.
+        assert_eq!(requested_ms(&mut led, |led| led.breath(90_001)), 90_001);
+
+        let predicted = led.heartbeat_duration_ms(4, 2, 60);
+        assert_eq!(
+            requested_ms(&mut led, |led| led.heartbeat(4, 2, 60)),
+            u64::from(predicted)
+        );
+
+        assert_eq!(requested_ms(&mut led, |led| led.staircase(5, 100)), 500);
+        assert_eq!(requested_ms(&mut led, |led| led.throb(100, 400, 2)), 1_000);
+        assert_eq!(requested_ms(&mut led, |led| led.fade(100, 750)), 750);
+
+        let env = Envelope { attack_ms: 30, hold_ms: 200, release_ms: 70, peak: 200 };
+        assert_eq!(requested_ms(&mut led, |led| led.play_envelope(&env)), 300);
+        assert_eq!(requested_ms(&mut led, |led| led.cycle(250, 4)), 1_000);
+    }
+
+    /// Delay for `with_delay` that accumulates the requested time.
+    #[derive(Default)]
+    struct MockDelay {
+        /// Total milliseconds requested
+        total_ms: u64,
+    }
+
+    impl DelayMs<u32> for MockDelay {
+        fn delay_ms(&mut self, ms: u32) {
+            self.total_ms += u64::from(ms);
+        }
+    }
+
+    /// Tests that an injected delay receives every wait of the effects.
     #[test]
-    fn test_invalid_parameters() {
-        let pin = MockPwm::new();
-        let led = LEDEffect::new(pin, 255, 5);
-        assert!(matches!(led, Err(Error::InvalidParameter)));
+    fn test_injected_delay() {
+        let mut led =
+            LEDEffect::with_delay(MockPwm::new(), 10, 110, MockDelay::default()).unwrap();
+        led.breath(6_000).unwrap();
+        assert_eq!(led.delay.total_ms, u64::from(led.breath_duration_ms(6_000)));
+
+        led.delay.total_ms = 0;
+        led.blink(100, 100, 5).unwrap();
+        assert_eq!(led.delay.total_ms, 1_000);
+        assert_eq!(led.pin.highest, 110);
+        assert_eq!(led.pin.get_duty(), 0);
+
+        // A yield hook takes the waits over from the delay.
+        led.delay.total_ms = 0;
+        REQUESTED_MS.with(|total| total.set(0));
+        led.set_yield(accumulating());
+        led.blink(100, 100, 5).unwrap();
+        assert_eq!(REQUESTED_MS.with(|total| total.get()), 1_000);
+        assert_eq!(led.delay.total_ms, 0);
+    }
+
+    /// Tests that `advance` never waits, even with a slew limit set.
+    #[test]
+    fn test_advance_ignores_slew() {
+        let mut led =
+            LEDEffect::with_delay(MockPwm::new(), 10, 110, MockDelay::default()).unwrap();
+        led.set_max_slew(1);
+        led.begin_fade(110, 2).unwrap();
+        assert!(led.advance());
+        assert!(!led.advance());
+        assert_eq!(led.pin.get_duty(), 110);
+        led.begin_breath(4).unwrap();
+        while led.advance() {}
+        assert_eq!(led.pin.get_duty(), 0);
+        assert_eq!(led.delay.total_ms, 0);
+        assert_eq!(led.max_slew(), 1);
+
+        // Blocking effects still slew.
+        led.on().unwrap();
+        assert_eq!(led.delay.total_ms, 109);
+    }
+
+    /// Tests that dithering alternates adjacent duties to average out fine levels.
+    #[test]
+    fn test_dither() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.fade(10, 0).unwrap();
+        led.fade(12, 100).unwrap();
+        assert_eq!(led.pin.writes, 1 + 2);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        led.set_dither(true);
+        led.fade(10, 0).unwrap();
+        led.pin.total = 0;
+        led.fade(12, 100).unwrap();
+        assert_eq!(led.pin.writes, 1 + 100);
+        assert_eq!(led.pin.get_duty(), 12);
+        // The exact levels 10 + 2 * step / 100 add up to 1101.
+        assert!(led.pin.total.abs_diff(1_101) <= 1, "{}", led.pin.total);
+        assert!(led.pin.highest <= 12);
+    }
+
+    /// Tests that `beat_sync` waits for each signalled beat before flashing.
+    #[test]
+    fn test_beat_sync() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let mut polls = 0;
+        let every_fifth = || {
+            polls += 1;
+            polls % 5 == 0
+        };
+        let waited = requested_ms(&mut led, |led| led.beat_sync(every_fifth, 50, 3));
+        // Four 1 ms polls before each beat, then a 50 ms decay.
+        assert_eq!(waited, 3 * (4 + 50));
+        assert_eq!(polls, 15);
+        assert_eq!(led.pin.get_duty(), 0);
+        let pin = led.destroy();
+        assert_eq!(pin.highest, 110);
+        assert_eq!(pin.lowest, 10);
+    }
+
+    /// Tests that the speed scales every delay without drift and rejects zero.
+    #[test]
+    fn test_speed() {
+        let mut led = LEDEffect::new(MockPwm::new(), 5, 255).unwrap();
+        assert_eq!(led.speed(), 100);
+        assert!(matches!(led.set_speed(0), Err(Error::InvalidParameter)));
+
+        led.set_speed(200).unwrap();
+        assert_eq!(led.breath_duration_ms(6_000), 3_000);
+        assert_eq!(requested_ms(&mut led, |led| led.breath(6_000)), 3_000);
+
+        led.set_speed(50).unwrap();
+        assert_eq!(requested_ms(&mut led, |led| led.staircase(4, 100)), 800);
+
+        // 1 ms steps at 300% still add up to a third of the fade.
+        led.set_speed(300).unwrap();
+        assert_eq!(requested_ms(&mut led, |led| led.fade(255, 250)), 83);
+        // Setting the speed drops the remainder carried over from the fade.
+        led.set_speed(300).unwrap();
+        let predicted = led.heartbeat_duration_ms(2, 2, 60);
+        assert_eq!(
+            requested_ms(&mut led, |led| led.heartbeat(2, 2, 60)),
+            u64::from(predicted)
+        );
+    }
+
+    /// Tests that the boot sequence fits its time bound and ends at `pwm_mid`.
+    #[test]
+    fn test_boot_sequence() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        assert_eq!(requested_ms(&mut led, |led| led.boot_sequence(1_000)), 1_000);
+        assert_eq!(led.pin.get_duty(), 110);
+        assert_eq!(led.pin.highest, 210);
+
+        // Slowed down, the sequence is cut short instead of overrunning.
+        led.set_speed(50).unwrap();
+        assert_eq!(requested_ms(&mut led, |led| led.boot_sequence(1_000)), 1_000);
+        assert_eq!(led.pin.get_duty(), 110);
+        assert_eq!(led.budget_ms, None);
+    }
+
+    /// Tests that eased fades overshoot within the range and end on the target.
+    #[test]
+    fn test_fade_eased() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        led.fade(10, 0).unwrap();
+        let waited = requested_ms(&mut led, |led| led.fade_eased(150, 200, Easing::EaseOutBack));
+        assert_eq!(waited, 200);
+        assert_eq!(led.pin.get_duty(), 150);
+        assert!(led.pin.highest > 155, "{}", led.pin.highest);
+
+        // Overshooting past `pwm_max` is clamped.
+        led.fade_eased(210, 100, Easing::EaseOutBack).unwrap();
+        assert_eq!(led.pin.highest, 210);
+
+        led.fade_eased(10, 100, Easing::EaseOutBounce).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        assert_eq!(led.pin.lowest, 10);
+        assert!(matches!(
+            led.fade_eased(211, 100, Easing::Linear),
+            Err(Error::InvalidParameter)
+        ));
+    }
+
+    /// Tests that custom-curve fades follow the curve and stay in range.
+    #[test]
+    fn test_fade_fn() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        led.fade(10, 0).unwrap();
+        let waited = requested_ms(&mut led, |led| led.fade_fn(110, 100, |t| t));
+        assert_eq!(waited, 100);
+        assert_eq!(led.pin.get_duty(), 110);
+
+        // A curve that stays put until the end jumps at the last step.
+        led.fade_fn(210, 100, |t| if t == u16::MAX { t } else { 0 }).unwrap();
+        assert_eq!(led.pin.get_duty(), 210);
+        assert_eq!(led.pin.timeline.iter().rev().nth(1).map(|&(_, duty)| duty), Some(110));
+
+        // A curve that runs backwards stays between the endpoints.
+        led.pin.lowest = u32::MAX;
+        led.fade_fn(200, 50, |t| u16::MAX - t).unwrap();
+        assert_eq!(led.pin.get_duty(), 210);
+        assert!(led.pin.lowest >= 200, "{}", led.pin.lowest);
+        assert!(matches!(led.fade_fn(5, 100, |t| t), Err(Error::InvalidParameter)));
+    }
+
+    /// Tests that a second LED can join a running breath in lockstep.
+    #[test]
+    fn test_sync_to() {
+        let mut leader = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        leader.set_phase(5_000);
+        leader.begin_breath(90).unwrap();
+        for _ in 0..30 {
+            leader.advance();
+        }
+
+        let mut follower = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let waited = requested_ms(&mut follower, |led| led.sync_to(leader.current_duty(), 50));
+        assert_eq!(waited, 50);
+        assert_eq!(follower.current_duty(), leader.current_duty());
+
+        follower.set_phase(leader.breath_phase().unwrap());
+        follower.begin_breath(90).unwrap();
+        while leader.advance() {
+            assert!(follower.advance());
+            assert_eq!(follower.pin.get_duty(), leader.pin.get_duty());
+        }
+        assert_eq!(leader.breath_phase(), None);
+
+        // Targets outside the range are clamped.
+        follower.sync_to(0, 10).unwrap();
+        assert_eq!(follower.pin.get_duty(), 10);
+        follower.sync_to(200, 10).unwrap();
+        assert_eq!(follower.pin.get_duty(), 110);
+    }
+
+    /// Tests that breathing in starts from off and holds the level.
+    #[test]
+    fn test_breathe_in_and_hold() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 200).unwrap();
+        led.on().unwrap();
+        led.pin.timeline.clear();
+        let waited = requested_ms(&mut led, |led| led.breathe_in_and_hold(3000, 120));
+        assert_eq!(waited, 3000);
+        assert_eq!(led.pin.timeline[0], (0, 0));
+        assert_eq!(led.pin.lowest, 11);
+        assert_eq!(led.pin.get_duty(), 120);
+        assert!(led.pin.highest <= 200);
+        assert!(matches!(led.breathe_in_and_hold(100, 201), Err(Error::InvalidParameter)));
+        assert!(matches!(led.breathe_in_and_hold(100, 9), Err(Error::InvalidParameter)));
+    }
+
+    /// Tests that a group drives every pin in lockstep with its own offset.
+    #[test]
+    fn test_group() {
+        let pins = [MockPwm::new(), MockPwm::new(), MockPwm::new()];
+        let mut panel = Group::new(pins, [0, -20, 100], 10, 200).unwrap();
+        panel.effect().set_end_state(EndState::Hold);
+
+        panel.fade(200, 50).unwrap();
+        panel.breath(300).unwrap();
+        assert!(matches!(panel.set_offset(3, 0), Err(Error::InvalidParameter)));
+        panel.set_offset(1, -5).unwrap();
+        assert_eq!(panel.offsets(), [0, -5, 100]);
+        panel.fade(100, 10).unwrap();
+
+        let [a, b, c] = panel.destroy();
+        assert_eq!((a.duty, b.duty, c.duty), (100, 95, 200));
+        assert_eq!(a.writes, b.writes);
+        assert_eq!((a.highest, b.highest, c.highest), (200, 180, 255));
+        assert_eq!((a.lowest, b.lowest), (10, 1));
+    }
+
+    /// Tests that `fault_code` blinks the code in groups separated by pauses.
+    #[test]
+    fn test_fault_code() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        assert!(matches!(led.fault_code(0, 1), Err(Error::InvalidParameter)));
+
+        let waited = requested_ms(&mut led, |led| led.fault_code(3, 2));
+        assert_eq!(waited, 2 * (3 * (200 + 300) + 1_500));
+        // Three blinks of on and off per group, then the final "off".
+        assert_eq!(led.pin.writes, 2 * 3 * 2 + 1);
+        assert_eq!(led.pin.highest, 110);
+        assert_eq!(led.pin.get_duty(), 0);
+
+        // Repeating forever still stops on a failed verified write.
+        led.set_verify(true);
+        led.pin.stuck = true;
+        assert!(matches!(led.fault_code(2, 0), Err(Error::Pwm)));
+    }
+
+    /// Tests that imperceptible updates are skipped but the endpoint is kept.
+    #[test]
+    fn test_jnd() {
+        let mut led = LEDEffect::new(MockPwm::with_max(1_000), 0, 1_000).unwrap();
+        led.fade(1_000, 1_000).unwrap();
+        assert_eq!(led.pin.writes, 1_000);
+
+        led.set_jnd(2);
+        assert_eq!(led.jnd(), 2);
+        led.fade(0, 1_000).unwrap();
+        led.pin.writes = 0;
+        led.pin.timeline.clear();
+        led.fade(999, 1_000).unwrap();
+        assert!(led.pin.writes < 200, "{}", led.pin.writes);
+        assert_eq!(led.pin.get_duty(), 999);
+        // Writes are dense near black and sparse near full brightness.
+        let dim = led.pin.timeline.iter().filter(|&&(_, duty)| duty < 100).count();
+        let bright = led.pin.timeline.iter().filter(|&&(_, duty)| duty >= 900).count();
+        assert!(dim > 2 * bright, "{} {}", dim, bright);
+    }
+
+    /// Tests that `distress` blinks SOS until cleared, also when inverted.
+    #[test]
+    fn test_distress() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let mut polls = 0;
+        led.set_yield(accumulating());
+        REQUESTED_MS.with(|total| total.set(0));
+        led.distress(|| {
+            polls += 1;
+            polls > 18
+        });
+        assert_eq!(REQUESTED_MS.with(|total| total.get()), 2 * 34 * 200);
+        assert_eq!(led.pin.peaks, 0);
+        let lit: std::vec::Vec<u64> = led.pin.timeline.windows(2)
+            .filter(|pair| pair[0].1 == 110)
+            .map(|pair| pair[1].0 - pair[0].0)
+            .collect();
+        assert_eq!(&lit[..9], &[200, 200, 200, 600, 600, 600, 200, 200, 200]);
+        assert_eq!(lit.len(), 18);
+        assert_eq!(led.pin.get_duty(), 0);
+
+        led.set_inverted(true);
+        led.set_verify(true);
+        led.pin.stuck = true;
+        let mut polls = 0;
+        led.distress(|| {
+            polls += 1;
+            polls > 3
+        });
+        assert_eq!(polls, 4);
+        led.pin.stuck = false;
+        led.distress(|| true);
+        assert_eq!(led.pin.get_duty(), 255);
+    }
+
+    /// Tests that adversarial parameters are rejected or saturate instead of panicking.
+    #[test]
+    fn test_adversarial_parameters() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 12).unwrap();
+        assert!(matches!(led.heartbeat(1, 1, 0), Err(Error::InvalidParameter)));
+        assert!(matches!(led.heartbeat_realistic(0, 1), Err(Error::InvalidParameter)));
+        assert!(matches!(led.staircase(0, 1), Err(Error::InvalidParameter)));
+        assert!(matches!(led.fault_code(0, 1), Err(Error::InvalidParameter)));
+        assert!(matches!(led.begin_breath(0), Err(Error::InvalidParameter)));
+        assert!(matches!(led.set_speed(0), Err(Error::InvalidParameter)));
+        assert_eq!(led.heartbeat_duration_ms(1, 1, 0), 0);
+        assert_eq!(led.heartbeat_duration_ms(u32::MAX, 1, 1), u32::MAX);
+        assert_eq!(led.heartbeat_duration_ms(u32::MAX, u32::MAX, 1), u32::MAX);
+
+        // Huge durations, beat counts and groupings only saturate.
+        led.set_yield(accumulating());
+        led.set_trough_hold_ms(u32::MAX);
+        assert_eq!(led.breath_duration_ms(u32::MAX), u32::MAX);
+        led.breath(u32::MAX).unwrap();
+        led.boot_sequence(u32::MAX).unwrap();
+        let heartbeat = EffectKind::Heartbeat {
+            flash_beats: u32::MAX,
+            grouped_as: u32::MAX,
+            bpm: 1,
+        };
+        led.run_for(heartbeat, 100_000).unwrap();
+        led.set_phase(u16::MAX);
+        led.begin_breath(u32::MAX).unwrap();
+        assert!(led.advance());
+        led.set_speed(1).unwrap();
+        led.fade(12, u32::MAX).unwrap();
+
+        // A cycle-counting delay gets long waits in pieces it can count.
+        let mut led = LEDEffect::with_delay(MockPwm::new(), 10, 12, CycleCounter(0)).unwrap();
+        led.set_clock_hz(168_000_000).unwrap();
+        led.blink(60_000, 60_000, 1).unwrap();
+        led.set_clock_hz(u32::MAX).unwrap();
+        led.blink(u32::MAX, 0, 1).unwrap();
+        assert_eq!(
+            led.delay.0,
+            120_000 * 168_000 + u64::from(u32::MAX) * u64::from(u32::MAX / 1_000)
+        );
+    }
+
+    /// Delay that counts cycles in 32 bits like `BusyWait`, and totals them.
+    struct CycleCounter(u64);
+
+    impl Delay for CycleCounter {
+        fn wait_ms(&mut self, ms: u32, cycles_per_ms: u32) {
+            let cycles = ms.checked_mul(cycles_per_ms).expect("cycle count overflows");
+            self.0 += u64::from(cycles);
+        }
+    }
+
+    /// Tests that `sample_at` matches what the effects write at the same point.
+    #[test]
+    fn test_sample_at() {
+        let led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        let breath = EffectKind::Breath { duration: 270 };
+        assert_eq!(led.sample_at(breath, 0), 10);
+        assert_eq!(led.sample_at(breath, 10_923), 55);
+        assert_eq!(led.sample_at(breath, 21_846), 100);
+        assert_eq!(led.sample_at(breath, 49_152), 10);
+
+        let stairs = EffectKind::Staircase { levels: 4, hold_ms: 100 };
+        assert_eq!(led.sample_at(stairs, 0), 10);
+        assert_eq!(led.sample_at(stairs, 40_000), 70);
+        assert_eq!(led.sample_at(stairs, u16::MAX), 100);
+
+        let heartbeat = EffectKind::Heartbeat { flash_beats: 2, grouped_as: 2, bpm: 60 };
+        assert_eq!(led.sample_at(heartbeat, 0), 100);
+        let stopped = EffectKind::Heartbeat { flash_beats: 2, grouped_as: 2, bpm: 0 };
+        assert_eq!(led.sample_at(stopped, 0), 10);
+
+        // Every sampled heartbeat level is the last one written by that time.
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        let total = requested_ms(&mut led, |led| led.heartbeat(2, 2, 60));
+        for t in (0..=u16::MAX).step_by(97) {
+            let ms = (total * u64::from(t)) >> 16;
+            let (_, written) = led.pin.timeline.iter().rev().find(|(at, _)| *at <= ms).unwrap();
+            assert_eq!(led.sample_at(heartbeat, t), *written, "t = {}", t);
+        }
+    }
+
+    /// Tests that the deadband holds the output steady for small input changes.
+    #[test]
+    fn test_deadband() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 255).unwrap();
+        led.set_deadband(1_000);
+        let readings = [30_000u16, 30_900, 29_100, 31_000, 31_500];
+        let mut iter = readings.iter().copied();
+        led.follow(|| iter.next().unwrap(), 3, 1).unwrap();
+        assert_eq!(led.pin.writes, 3);
+        assert_eq!(led.pin.highest, led.pin.lowest);
+
+        led.follow(|| iter.next().unwrap(), 2, 1).unwrap();
+        assert_eq!(led.pin.get_duty(), 123);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 255).unwrap();
+        led.set_deadband(1_000);
+        led.set_progress(50, 0).unwrap();
+        led.set_progress(51, 0).unwrap();
+        assert_eq!(led.pin.get_duty(), 127);
+        led.set_progress(52, 0).unwrap();
+        assert_eq!(led.pin.get_duty(), 133);
+    }
+
+    /// Tests that frames follow the effect's shape without touching the pin.
+    #[test]
+    fn test_next_frame() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        assert_eq!(led.next_frame(16), None);
+        let zero_bpm = EffectKind::Heartbeat { flash_beats: 1, grouped_as: 1, bpm: 0 };
+        assert!(matches!(led.begin_frames(zero_bpm), Err(Error::InvalidParameter)));
+
+        let breath = EffectKind::Breath { duration: 270 };
+        led.begin_frames(breath).unwrap();
+        assert_eq!(led.next_frame(0), Some(10));
+        assert_eq!(led.next_frame(45), Some(55));
+        assert_eq!(led.next_frame(45), Some(100));
+        assert_eq!(led.next_frame(179), Some(10));
+        assert_eq!(led.next_frame(1), None);
+        assert_eq!(led.next_frame(1), None);
+        assert_eq!(led.pin.writes, 0);
+
+        // Frames are ready for the pin: dimmed and inverted.
+        led.set_master(Percent::from_literal(50));
+        led.set_inverted(true);
+        led.begin_frames(breath).unwrap();
+        assert_eq!(led.next_frame(90), Some(255 - 55));
+    }
+
+    /// Tests that the final "off" write uses the configured off duty.
+    #[test]
+    fn test_off_duty() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 200).unwrap();
+        assert_eq!(led.off_duty(), 0);
+        assert!(matches!(led.set_off_duty(11), Err(Error::InvalidParameter)));
+
+        led.set_off_duty(10).unwrap();
+        led.breath(30).unwrap();
+        assert_eq!(led.pin.duty, 10);
+        led.off().unwrap();
+        assert_eq!(led.pin.duty, 10);
+
+        // Inverted, the off duty is still a valid value for the pin.
+        led.set_inverted(true);
+        led.heartbeat(1, 1, 6000).unwrap();
+        let max = led.pin.get_max_duty();
+        assert!((0..=max).contains(&led.pin.duty));
+        assert_eq!(led.pin.duty, max - 10);
+    }
+
+    /// Tests that error codes are stable and can be blinked.
+    #[test]
+    fn test_error_code() {
+        assert_eq!(Error::Pwm.code(), 1);
+        assert_eq!(Error::InvalidParameter.code(), 2);
+
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 100).unwrap();
+        let error = led.staircase(0, 10).unwrap_err();
+        led.fault_code(error.code(), 1).unwrap();
+        let blinks = led.pin.timeline.iter().filter(|&&(_, duty)| duty == 100).count();
+        assert_eq!(blinks, 2);
+    }
+
+    /// Tests that lit duties in the dead zone are lifted and off stays off.
+    #[test]
+    fn test_min_on() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 100).unwrap();
+        assert!(matches!(led.set_min_on(101), Err(Error::InvalidParameter)));
+        led.set_min_on(10).unwrap();
+
+        led.fade(100, 100).unwrap();
+        led.fade(0, 100).unwrap();
+        assert_eq!(led.pin.lowest, 10);
+        assert_eq!(led.pin.get_duty(), 0);
+        assert!(led.pin.timeline.iter().any(|&(_, duty)| duty == 11));
+
+        led.set_inverted(true);
+        led.set_gamma(false);
+        led.set_level(3_000).unwrap();
+        assert_eq!(led.pin.get_duty(), 255 - 10);
+    }
+
+    /// Pick one of `choices` with a seeded generator.
+    fn pick<T: Copy>(rng: &mut TestRng, choices: &[T]) -> T {
+        choices[rng.next_u32() as usize % choices.len()]
+    }
+
+    /// Property test: random settings and parameters, valid or not, never
+    /// panic, never write outside `[0, get_max_duty()]` and always return.
+    #[test]
+    fn test_effect_properties() {
+        const DUTIES: [u32; 7] = [0, 1, 2, 127, 200, 255, 256];
+        const DURATIONS: [u32; 7] = [0, 1, 2, 7, 1_000, 65_535, u32::MAX];
+        const COUNTS: [u32; 5] = [0, 1, 2, 3, 7];
+        const BPMS: [u32; 7] = [0, 1, 2, 60, 255, 60_001, u32::MAX];
+
+        let mut rng = TestRng(0x5EED);
+        for round in 0..2_000 {
+            let (min, max) = (pick(&mut rng, &DUTIES), pick(&mut rng, &DUTIES));
+            let Ok(mut led) = LEDEffect::new(MockPwm::new(), min, max) else {
+                continue;
+            };
+            let _ = led.set_speed(pick(&mut rng, &[0, 1, 100, 1_000, u16::MAX]));
+            let _ = led.set_safe_max(pick(&mut rng, &DUTIES));
+            let _ = led.set_min_on(pick(&mut rng, &DUTIES));
+            let _ = led.set_off_duty(pick(&mut rng, &DUTIES));
+            led.set_master(Percent::new(pick(&mut rng, &[0, 1, 50, 100])).unwrap());
+            led.set_inverted(rng.next_u32() & 1 == 1);
+            led.set_phase(rng.next_u32() as u16);
+            led.set_trough_hold_ms(pick(&mut rng, &[0, 1, 500]));
+            led.set_end_state(pick(&mut rng, &[EndState::Off, EndState::Min, EndState::Hold]));
+
+            let duty = pick(&mut rng, &DUTIES);
+            let duration = pick(&mut rng, &DURATIONS);
+            let (a, b) = (pick(&mut rng, &COUNTS), pick(&mut rng, &COUNTS));
+            let bpm = pick(&mut rng, &BPMS);
+            let _ = match round % 12 {
+                0 => led.breath(duration),
+                1 => led.heartbeat(a, b, bpm),
+                2 => led.heartbeat_realistic(bpm, a),
+                3 => led.staircase(a, duration),
+                4 => led.throb(duration, pick(&mut rng, &DURATIONS), a),
+                5 => led.fade(duty, duration),
+                // One write per millisecond: keep the mock's log finite.
+                6 => {
+                    let easing = pick(&mut rng, &[Easing::EaseOutBack, Easing::EaseOutBounce]);
+                    led.fade_eased(duty, duration.min(65_535), easing)
+                }
+                7 => led.cycle(duration, a),
+                8 => led.fault_code(pick(&mut rng, &[0, 1, 3, u8::MAX]), b.max(1)),
+                9 => led.boot_sequence(duration),
+                10 => led.breathe_in_and_hold(duration, duty),
+                _ => led.set_progress(rng.next_u32() as u8, duration),
+            };
+            assert!(led.pin.highest <= led.pin.get_max_duty(), "round {}", round);
+        }
+    }
+
+    /// Tests that a fixed frame rate sets the number of steps, not the timing.
+    #[test]
+    fn test_fps() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 255).unwrap();
+        assert!(matches!(led.set_fps(0), Err(Error::InvalidParameter)));
+        led.set_fps(50).unwrap();
+        assert_eq!(led.fps(), Some(50));
+
+        let waited = requested_ms(&mut led, |led| led.fade(255, 1_000));
+        assert_eq!(waited, 1_000);
+        assert_eq!(led.pin.writes, 50);
+        assert_eq!(led.pin.get_duty(), 255);
+
+        // Three frames of a 30 fps breath per 100 ms, plus the final "off".
+        led.set_fps(30).unwrap();
+        led.pin.writes = 0;
+        let waited = requested_ms(&mut led, |led| led.breath(3_000));
+        assert_eq!(waited, 3_000);
+        assert_eq!(led.pin.writes, 90 + 1);
+
+        led.clear_fps();
+        led.pin.writes = 0;
+        led.fade(128, 1_000).unwrap();
+        assert_eq!(led.pin.writes, 128);
+    }
+
+    /// Tests that the low-update power mode draws far fewer frames.
+    #[test]
+    fn test_power_mode() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 255).unwrap();
+        assert_eq!(led.power_mode(), PowerMode::Normal);
+        led.set_power_mode(PowerMode::LowUpdate);
+        led.set_dither(true);
+
+        let waited = requested_ms(&mut led, |led| led.fade(255, 1_000));
+        assert_eq!(waited, 1_000);
+        assert_eq!(led.pin.writes, 25);
+        assert_eq!(led.pin.get_duty(), 255);
+
+        led.set_power_mode(PowerMode::Normal);
+        led.set_dither(false);
+        led.pin.writes = 0;
+        led.fade(0, 1_000).unwrap();
+        assert_eq!(led.pin.writes, 255);
+    }
+
+    /// Tests that finished effects report their brightness for persistence.
+    #[test]
+    fn test_on_settle() {
+        use core::sync::atomic::{AtomicU8, Ordering};
+        // `u8::MAX` stands for "nothing reported".
+        let settled = AtomicU8::new(u8::MAX);
+        let mut record = |percent| settled.store(percent, Ordering::Relaxed);
+        let take = |settled: &AtomicU8| {
+            Some(settled.swap(u8::MAX, Ordering::Relaxed)).filter(|&percent| percent != u8::MAX)
+        };
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        led.on_settle(&mut record);
+        led.fade(110, 50).unwrap();
+        assert_eq!(take(&settled), Some(50));
+        led.breath(30).unwrap();
+        assert_eq!(take(&settled), Some(0));
+
+        // The reported value restores the same level.
+        led.set_progress(37, 0).unwrap();
+        assert_eq!(take(&settled), Some(37));
+
+        led.set_verify(true);
+        led.pin.stuck = true;
+        assert!(led.on().is_err());
+        assert_eq!(take(&settled), None);
+
+        led.clear_settle();
+        led.pin.stuck = false;
+        led.on().unwrap();
+        assert_eq!(take(&settled), None);
+    }
+
+    /// Tests that colours go through each channel's own gamma table.
+    #[test]
+    fn test_rgb_gamma_tables() {
+        static LINEAR: [u16; 256] = {
+            let mut table = [0; 256];
+            let mut i = 0;
+            while i < 256 {
+                table[i] = (i * 257) as u16;
+                i += 1;
+            }
+            table
+        };
+
+        let mut led = RgbEffect::new(MockPwm::new(), MockPwm::new(), MockPwm::new()).unwrap();
+        led.set_color(255, 128, 0).unwrap();
+        assert_eq!(led.color(), [255, 128, 0]);
+        let (r, g, b) = (led.red().pin.duty, led.green().pin.duty, led.blue().pin.duty);
+        assert_eq!((r, b), (255, 0));
+        assert!((50..60).contains(&g), "{}", g);
+
+        led.set_gamma_tables(&gamma::GAMMA_2_2, &LINEAR, &LINEAR);
+        led.red().set_yield(accumulating());
+        REQUESTED_MS.with(|total| total.set(0));
+        led.fade_to(128, 128, 128, 300).unwrap();
+        assert_eq!(REQUESTED_MS.with(|total| total.get()), 300);
+        assert_eq!(led.green().pin.duty, 128);
+        assert_eq!(led.blue().pin.duty, 128);
+        assert!((50..60).contains(&led.red().pin.duty));
+        assert!(led.blue().pin.writes > 100);
+    }
+
+    /// Tests that raising the flag stops an effect and applies the end state.
+    #[test]
+    fn test_cancellable() {
+        let cancel = Cancel::new();
+        // Raises the flag once 100 ms have passed, like an ISR.
+        let mut wait = |ms: u32| {
+            accumulate_delay(ms);
+            if REQUESTED_MS.with(|total| total.get()) >= 100 {
+                cancel.cancel();
+            }
+        };
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        led.set_yield(&mut wait);
+        REQUESTED_MS.with(|total| total.set(0));
+        led.breath_cancellable(3_000, &cancel).unwrap();
+        assert!(cancel.is_cancelled());
+        assert!((100..110).contains(&REQUESTED_MS.with(|total| total.get())));
+        assert_eq!(led.pin.get_duty(), 0);
+
+        // Still raised: the next effect returns after one step.
+        led.pin.writes = 0;
+        let staircase = EffectKind::Staircase { levels: 5, hold_ms: 10 };
+        led.run_for_cancellable(staircase, 1_000, &cancel).unwrap();
+        assert_eq!(led.pin.writes, 2);
+
+        // The flag is only watched during the call.
+        led.pin.writes = 0;
+        led.run_kind(staircase).unwrap();
+        assert_eq!(led.pin.writes, 6);
+
+        cancel.reset();
+        led.clear_yield();
+        led.set_end_state(EndState::Hold);
+        led.fade_cancellable(110, 50, &cancel).unwrap();
+        assert_eq!(led.pin.get_duty(), 110);
+    }
+
+    /// Tests that a countdown dims with the time left and ends off.
+    #[test]
+    fn test_countdown() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 255).unwrap();
+        led.set_end_state(EndState::Hold);
+        let waited = requested_ms(&mut led, |led| led.countdown(1_000));
+        assert_eq!(waited, 1_000);
+        assert_eq!(led.pin.timeline.first(), Some(&(0, 255)));
+        assert_eq!(led.pin.timeline.last(), Some(&(1_000, 0)));
+        assert!(led.pin.timeline.windows(2).all(|pair| pair[1].1 <= pair[0].1));
+        // Perceptually half way: well below half the duty.
+        let half = led.pin.timeline.iter().find(|&&(time, _)| time >= 500).unwrap();
+        assert!((50..70).contains(&half.1), "{:?}", half);
+
+        led.set_gamma(false);
+        led.set_fps(10).unwrap();
+        led.pin.timeline.clear();
+        led.countdown(1_000).unwrap();
+        let duties: std::vec::Vec<u32> = led.pin.timeline.iter().map(|&(_, duty)| duty).collect();
+        assert_eq!(duties, [255, 229, 204, 178, 153, 127, 102, 76, 51, 25, 0]);
+    }
+
+    /// Tests that `auto` derives the range from the pin.
+    #[test]
+    fn test_auto_range() {
+        let led = LEDEffect::auto(MockPwm::new()).unwrap();
+        assert_eq!((led.pwm_min, led.pwm_max), (2, 255));
+        let led = LEDEffect::auto(MockPwm::with_max(3)).unwrap();
+        assert_eq!((led.pwm_min, led.pwm_max), (1, 3));
+        let led = LEDEffect::auto(MockPwm::with_max(65_535)).unwrap();
+        assert_eq!(led.pwm_min, 655);
+        assert!(LEDEffect::auto(MockPwm::with_max(2)).is_err());
+    }
+
+    /// Tests that rendering in slices never waits longer than asked.
+    #[test]
+    fn test_render_slice() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        assert_eq!(led.render_slice(40), RenderProgress::Done);
+        led.begin_frames(EffectKind::Breath { duration: 300 }).unwrap();
+        assert_eq!(led.render_slice(0), RenderProgress::Pending);
+        assert_eq!(led.pin.writes, 0);
+
+        let mut slices = 0;
+        let mut total = 0;
+        loop {
+            let mut progress = RenderProgress::Pending;
+            let waited = requested_ms(&mut led, |led| {
+                progress = led.render_slice(40);
+                Ok(())
+            });
+            assert!(waited <= 40, "{}", waited);
+            total += waited;
+            slices += 1;
+            if progress == RenderProgress::Done {
+                break;
+            }
+            assert_eq!(led.status().progress, (total * 100 / 300) as u8);
+        }
+        assert_eq!((slices, total), (8, 300));
+        assert_eq!(led.pin.highest, 110);
+        assert_eq!(led.pin.get_duty(), 0);
+        assert_eq!(led.status().kind, None);
+
+        // One write per frame at a fixed frame rate.
+        led.set_fps(50).unwrap();
+        led.begin_frames(EffectKind::Breath { duration: 300 }).unwrap();
+        let writes = led.pin.writes;
+        assert_eq!(requested_ms(&mut led, |led| {
+            while led.render_slice(15) == RenderProgress::Pending {}
+            Ok(())
+        }), 300);
+        assert_eq!(led.pin.writes - writes, 15 + 1);
+    }
+
+    /// Tests that a pin reporting a zero maximum duty fails cleanly.
+    #[test]
+    fn test_zero_max_duty() {
+        assert!(matches!(LEDEffect::auto(MockPwm::with_max(0)), Err(Error::Pwm)));
+        assert!(matches!(
+            RgbEffect::new(MockPwm::new(), MockPwm::with_max(0), MockPwm::new()),
+            Err(Error::Pwm)
+        ));
+
+        // An explicit range still works, and the level mappings do not panic.
+        let mut led = LEDEffect::new(MockPwm::with_max(0), 10, 110).unwrap();
+        led.set_progress(50, 0).unwrap();
+        led.set_level(u16::MAX).unwrap();
+        led.set_inverted(true);
+        led.on().unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+    }
+
+    /// Tests that `run_kind` runs the effect a stored `EffectKind` names.
+    #[test]
+    fn test_run_kind() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let mut selected = EffectKind::Breath { duration: 300 };
+        let waited = requested_ms(&mut led, |led| led.run_kind(selected));
+        assert_eq!(waited, 300);
+        assert_eq!(led.pin.highest, 110);
+
+        selected = EffectKind::Staircase { levels: 4, hold_ms: 25 };
+        let waited = requested_ms(&mut led, |led| led.run_kind(selected));
+        assert_eq!(waited, 100);
+        selected = EffectKind::Heartbeat { flash_beats: 1, grouped_as: 1, bpm: 0 };
+        assert!(matches!(led.run_kind(selected), Err(Error::InvalidParameter)));
+    }
+
+    /// Tests that `run_kind`, the frame length and `Config` cover the
+    /// parameterised effects.
+    #[test]
+    fn test_run_kind_parameterised() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let kinds = [
+            (EffectKind::Fade { from: 0, to: LEVEL_MAX, duration_ms: 200 }, 200),
+            (EffectKind::Blink { on_ms: 100, off_ms: 50, times: 3 }, 450),
+            (EffectKind::Throb { attack_ms: 50, decay_ms: 100, cycles: 2 }, 300),
+            (EffectKind::Cycle { period_ms: 200, cycles: 2 }, 400),
+            (EffectKind::Countdown { total_ms: 300 }, 300),
+            (EffectKind::HeartbeatRealistic { bpm: 60, beats: 2 }, 2_000),
+            (
+                EffectKind::LayeredBreath { small_cycles: 1, small_low: 32_768, big_cycles: 1 },
+                5_500,
+            ),
+            (EffectKind::Sparkle { duration_ms: 500, density: 128, seed: 7 }, 500),
+            (EffectKind::OrganicBreath { duration_ms: 5_000, seed: 3 }, 5_000),
+            (EffectKind::FaultCode { code: 2, repeats: 1 }, 2_500),
+            (EffectKind::Distress { repeats: 2 }, 2 * 6_800),
+        ];
+        for (kind, expected) in kinds {
+            let waited = requested_ms(&mut led, |led| led.run_kind(kind));
+            assert_eq!(waited, expected, "{:?}", kind);
+            assert_eq!(led.kind_length_ms(kind), Some(expected), "{:?}", kind);
+
+            let mut config = led.to_config();
+            config.set_default_effect(Some(kind));
+            assert_eq!(config.default_effect().unwrap(), Some(kind));
+        }
+        let waited = requested_ms(&mut led, |led| led.run_for(kinds[1].0, 1_000));
+        assert_eq!(waited, 1_000);
+
+        let rejected = [
+            EffectKind::Fade { from: 0, to: LEVEL_MAX + 1, duration_ms: 200 },
+            EffectKind::HeartbeatRealistic { bpm: 0, beats: 1 },
+            EffectKind::LayeredBreath { small_cycles: 1, small_low: 0, big_cycles: 1 },
+            EffectKind::Sparkle { duration_ms: 500, density: 256, seed: 7 },
+            EffectKind::FaultCode { code: 256, repeats: 1 },
+        ];
+        for kind in rejected {
+            assert!(matches!(led.run_kind(kind), Err(Error::InvalidParameter)), "{:?}", kind);
+            assert!(led.begin_frames(kind).is_err(), "{:?}", kind);
+        }
+        let forever = EffectKind::FaultCode { code: 1, repeats: 0 };
+        assert!(matches!(led.begin_frames(forever), Err(Error::InvalidParameter)));
+    }
+
+    /// Tests that the parameterised effects sample to the levels they write.
+    #[test]
+    fn test_sample_at_parameterised() {
+        let kinds = [
+            EffectKind::Blink { on_ms: 100, off_ms: 50, times: 3 },
+            EffectKind::Sparkle { duration_ms: 2_000, density: 100, seed: 42 },
+            EffectKind::FaultCode { code: 3, repeats: 2 },
+            EffectKind::Distress { repeats: 2 },
+        ];
+        for kind in kinds {
+            let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+            let total = requested_ms(&mut led, |led| led.run_kind(kind));
+            for t in (0..=u16::MAX).step_by(97) {
+                let ms = (total * u64::from(t)) >> 16;
+                let (_, written) =
+                    led.pin.timeline.iter().rev().find(|(at, _)| *at <= ms).unwrap();
+                assert_eq!(led.sample_at(kind, t), *written, "{:?} at t = {}", kind, t);
+            }
+        }
+
+        // The same seed replays the same pattern.
+        let sparkle = EffectKind::Sparkle { duration_ms: 500, density: 128, seed: 7 };
+        let mut first = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        let mut second = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        requested_ms(&mut first, |led| led.run_kind(sparkle));
+        requested_ms(&mut second, |led| led.run_kind(sparkle));
+        assert_eq!(first.pin.timeline, second.pin.timeline);
+
+        let led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let throb = EffectKind::Throb { attack_ms: 100, decay_ms: 100, cycles: 1 };
+        assert_eq!(led.sample_at(throb, 0), 10);
+        assert_eq!(led.sample_at(throb, 1 << 14), 60);
+        assert_eq!(led.sample_at(throb, 1 << 15), 110);
+        let fade = EffectKind::Fade { from: LEVEL_MAX, to: 0, duration_ms: 100 };
+        assert_eq!(led.sample_at(fade, 0), 110);
+        assert_eq!(led.sample_at(fade, u16::MAX), 11);
+        let layered =
+            EffectKind::LayeredBreath { small_cycles: 1, small_low: 32_768, big_cycles: 1 };
+        assert_eq!(led.sample_at(layered, 0), 10);
+        // The deep breath rises from the shallow one's low of 60.
+        assert_eq!(led.sample_at(layered, 29_790), 85);
+        assert_eq!(led.sample_at(layered, 41_705), 110);
+    }
+
+    /// Tests that long repeating effects do not drift from their nominal length.
+    #[test]
+    fn test_no_drift_over_100_cycles() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        // A decay of 332 ms over 101 duty steps used to round to 3 ms a step.
+        let waited = requested_ms(&mut led, |led| led.heartbeat(100, 1, 60));
+        assert_eq!(waited, 100 * (3 * 55 + 332 + 332));
+        assert_eq!(waited, u64::from(led.heartbeat_duration_ms(100, 1, 60)));
+
+        let cycle = EffectKind::Breath { duration: 1_234 };
+        let waited = requested_ms(&mut led, |led| led.run_for(cycle, 100 * 1_234));
+        assert_eq!(waited, 100 * 1_234);
+        // Speed scaling carries its rounding remainder from cycle to cycle.
+        led.set_speed(70).unwrap();
+        let waited = requested_ms(&mut led, |led| (0..100).try_for_each(|_| led.breath(1_234)));
+        assert_eq!(waited, 100 * 1_234 * 100 / 70);
+    }
+
+    /// Tests that a sequence runs its steps in order.
+    #[test]
+    fn test_sequence_order() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let seq: EffectSequence<u32, 4> = [
+            EffectStep::Fade { target: 60, duration_ms: 100 },
+            EffectStep::Hold { duration_ms: 200 },
+            EffectStep::Heartbeat { flash_beats: 1, grouped_as: 1, bpm: 60 },
+            EffectStep::Fade { target: 10, duration_ms: 100 },
+        ]
+        .into_iter()
+        .collect();
+        let heartbeat = u64::from(led.heartbeat_duration_ms(1, 1, 60));
+        let waited = requested_ms(&mut led, |led| seq.run(led));
+        assert_eq!(waited, 100 + 200 + heartbeat + 100);
+
+        let timeline = &led.pin.timeline;
+        let faded = timeline.iter().position(|&(_, duty)| duty == 60).unwrap();
+        assert!(timeline[faded].0 <= 100);
+        // Nothing is written while holding; the heartbeat flashes after it.
+        assert_eq!(timeline[faded + 1], (300, 110));
+        assert!(timeline[faded + 1..].iter().all(|&(at, _)| at >= 300));
+        // The final fade starts once the heartbeat is over.
+        let &(at, duty) = timeline.last().unwrap();
+        assert!(at > 300 + heartbeat);
+        assert_eq!(duty, 10);
+    }
+
+    /// Tests that a hold step waits without writing.
+    #[test]
+    fn test_sequence_hold() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let mut seq: EffectSequence<u32, 2> = EffectSequence::new();
+        seq.push(EffectStep::Hold { duration_ms: 250 }).unwrap();
+        seq.push(EffectStep::Hold { duration_ms: 0 }).unwrap();
+        assert_eq!(requested_ms(&mut led, |led| seq.run(led)), 250);
+        assert!(led.pin.timeline.is_empty());
+        assert!(seq.push(EffectStep::Hold { duration_ms: 1 }).is_err());
+    }
+
+    /// Tests that a failing step stops the sequence.
+    #[test]
+    fn test_sequence_error_stops() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let seq: EffectSequence<u32, 3> = [
+            EffectStep::Hold { duration_ms: 100 },
+            EffectStep::Fade { target: 200, duration_ms: 100 },
+            EffectStep::Breath { duration: 300 },
+        ]
+        .into_iter()
+        .collect();
+        led.set_yield(accumulating());
+        REQUESTED_MS.with(|total| total.set(0));
+        assert!(matches!(seq.run(&mut led), Err(Error::InvalidParameter)));
+        assert_eq!(REQUESTED_MS.with(|total| total.get()), 100);
+        assert!(led.pin.timeline.is_empty());
+    }
+
+    /// Tests that an RGB flash returns to the colour shown before it.
+    #[test]
+    fn test_rgb_flash() {
+        let mut led = RgbEffect::new(MockPwm::new(), MockPwm::new(), MockPwm::new()).unwrap();
+        led.set_color(0, 0, 255).unwrap();
+        led.red().set_yield(accumulating());
+        REQUESTED_MS.with(|total| total.set(0));
+        led.flash(0, 255, 0, 150).unwrap();
+        assert_eq!(REQUESTED_MS.with(|total| total.get()), 300);
+        assert_eq!(led.color(), [0, 0, 255]);
+        assert_eq!(led.green().pin.highest, 255);
+        assert_eq!((led.green().pin.duty, led.blue().pin.duty), (0, 255));
+        assert_eq!(led.blue().pin.timeline[1], (0, 0));
+    }
+
+    /// Tests that a composed source is sampled on schedule and mapped onto the range.
+    #[test]
+    fn test_play_source() {
+        use source::{Constant, Triangle};
+
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 110).unwrap();
+        let mut source = Triangle::new(1_000).mul(Constant(u16::MAX / 2));
+        assert!(led.play_source(&mut source, 1_000, 0).is_err());
+
+        let waited = requested_ms(&mut led, |led| led.play_source(&mut source, 1_050, 100));
+        assert_eq!(waited, 1_050);
+        // Eleven samples plus the final "off"
+        assert_eq!(led.pin.writes, 12);
+        assert_eq!(led.pin.highest, 60);
+        assert_eq!(led.pin.timeline[0], (0, 10));
+        assert_eq!(led.pin.get_duty(), 0);
+    }
+
+    /// Tests that the shallow breaths stay above `small_low`.
+    #[test]
+    fn test_layered_breath() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 210).unwrap();
+        assert!(led.layered_breath(1, 10, 1).is_err());
+        assert!(led.layered_breath(1, 210, 1).is_err());
+
+        let waited = requested_ms(&mut led, |led| led.layered_breath(3, 150, 1));
+        assert_eq!(waited, 3 * 1_500 + 4_000);
+        let small: std::vec::Vec<u32> = led.pin.timeline.iter()
+            .filter(|&&(t, _)| (750..4_500).contains(&t))
+            .map(|&(_, duty)| duty)
+            .collect();
+        assert!(small.iter().all(|&duty| duty >= 150));
+        assert_eq!(led.pin.highest, 210);
+        assert_eq!(led.pin.lowest, 10);
+        assert_eq!(led.pin.get_duty(), 0);
+    }
+
+    /// Tests that effects on a near-`u32::MAX` duty range finish quickly and
+    /// land exactly on their endpoints.
+    #[test]
+    fn test_wide_duty_range() {
+        let max = u32::MAX - 1;
+        let mut led = LEDEffect::new(MockPwm::with_max(max), 1, max).unwrap();
+
+        led.set_end_state(EndState::Min);
+        let waited = requested_ms(&mut led, |led| led.breath(3_000));
+        assert_eq!(waited, 3_000);
+        assert_eq!(led.pin.highest, max);
+        assert_eq!(led.pin.get_duty(), 1);
+        assert!(led.pin.writes <= 3_000 + 1);
+
+        led.pin.writes = 0;
+        let waited = requested_ms(&mut led, |led| led.heartbeat(2, 1, 60));
+        assert_eq!(waited, u64::from(led.heartbeat_duration_ms(2, 1, 60)));
+        assert!(led.pin.writes <= 2_000);
+
+        led.fade(max, 500).unwrap();
+        assert_eq!(led.pin.get_duty(), max);
+        led.set_progress(50, 0).unwrap();
+        assert!(led.pin.get_duty().abs_diff(max / 2) < max / 10_000);
+        led.set_level(u16::MAX).unwrap();
+        assert_eq!(led.pin.get_duty(), max);
+        led.throb(100, 100, 2).unwrap();
+        led.cycle(100, 2).unwrap();
+        led.staircase(5, 10).unwrap();
+        assert_eq!(led.pin.highest, max);
+        assert_eq!(led.sample_at(EffectKind::Breath { duration: 3_000 }, 0), 1);
+    }
+
+    /// Tests that `status` follows both non-blocking state machines.
+    #[test]
+    fn test_status() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 100).unwrap();
+        assert_eq!(led.status(), EffectStatus { kind: None, progress: 100, brightness: 0 });
+
+        led.begin_fade(100, 4).unwrap();
+        assert_eq!(led.status().progress, 0);
+        led.advance();
+        assert_eq!((led.status().progress, led.status().brightness), (25, 25));
+
+        let effect = EffectKind::Breath { duration: 900 };
+        led.begin_frames(effect).unwrap();
+        led.next_frame(0);
+        assert_eq!(led.status(), EffectStatus { kind: Some(effect), progress: 0, brightness: 0 });
+        led.next_frame(300);
+        assert_eq!(led.status(), EffectStatus { kind: Some(effect), progress: 33, brightness: 100 });
+        while led.next_frame(100).is_some() {}
+        assert_eq!(led.status().kind, None);
+    }
+
+    /// Tests the duty conversion helpers at the edges of each duty type.
+    #[test]
+    fn test_duty_conversions() {
+        type Wide = LEDEffect<'static, MockPwm>;
+        assert_eq!(Wide::to_u32(u32::MAX), u32::MAX);
+        assert_eq!(Wide::from_u32(u32::MAX), u32::MAX);
+        assert_eq!(Wide::from_u32(0), 0);
+
+        type Narrow = LEDEffect<'static, SoftPwm<embedded_hal_mock::pin::Mock>>;
+        assert_eq!(Narrow::to_u32(u16::MAX), 65_535);
+        assert_eq!(Narrow::from_u32(65_535), u16::MAX);
+        assert_eq!(Narrow::from_u32(65_536), u16::MAX);
+        assert_eq!(Narrow::from_u32(u32::MAX), u16::MAX);
+    }
+
+    /// Tests that each `EndState` controls the final duty left by an effect.
+    #[test]
+    fn test_end_state() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        assert_eq!(led.end_state(), EndState::Off);
+        led.breath(600).unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+
+        led.set_end_state(EndState::Min);
+        led.breath(600).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+
+        led.set_end_state(EndState::Hold);
+        led.heartbeat(1, 1, 60).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        led.on().unwrap();
+        led.sparkle(&mut TestRng(1), 0, 0).unwrap();
+        assert_eq!(led.pin.get_duty(), 100);
+    }
+
+    /// Tests that the seconds-based shorthands scale and saturate.
+    #[test]
+    fn test_secs() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        assert_eq!(requested_ms(&mut led, |led| led.breath_secs(3)), 3_000);
+        assert_eq!(requested_ms(&mut led, |led| led.fade_secs(100, 2)), 2_000);
+        assert_eq!(led.pin.get_duty(), 100);
+        let effect = EffectKind::Breath { duration: 1_000 };
+        assert_eq!(requested_ms(&mut led, |led| led.run_for_secs(effect, 5)), 5_000);
+        assert_eq!(secs_to_ms(4_294_967), 4_294_967_000);
+        assert_eq!(secs_to_ms(4_294_968), u32::MAX);
+    }
+
+    /// Tests that instant changes are spread out at the maximum slew rate.
+    #[test]
+    fn test_max_slew() {
+        let mut led = LEDEffect::new(MockPwm::new(), 0, 100).unwrap();
+        led.set_max_slew(10);
+        assert_eq!(led.max_slew(), 10);
+        let waited = requested_ms(&mut led, |led| led.on());
+        assert_eq!(waited, 9);
+        let steps: std::vec::Vec<u32> = led.pin.timeline.iter().map(|&(_, duty)| duty).collect();
+        assert_eq!(steps, [10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
+        assert!(led.pin.timeline.windows(2).all(|pair| pair[1].0 - pair[0].0 <= 1));
+
+        // A slow fade is not slowed down further.
+        assert_eq!(requested_ms(&mut led, |led| led.fade(0, 100)), 100);
+        led.set_max_slew(0);
+        assert_eq!(requested_ms(&mut led, |led| led.on()), 0);
+        assert_eq!(led.pin.get_duty(), 100);
+    }
+
+    /// Tests that `_with_end` variants override the end state for one call.
+    #[test]
+    fn test_with_end() {
+        let mut led = LEDEffect::new(MockPwm::new(), 10, 100).unwrap();
+        led.breath_with_end(600, EndState::Min).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        assert_eq!(led.end_state(), EndState::Off);
+        led.breath(600).unwrap();
+        assert_eq!(led.pin.get_duty(), 0);
+
+        led.heartbeat_with_end(1, 1, 60, EndState::Min).unwrap();
+        assert_eq!(led.pin.get_duty(), 10);
+        let effect = EffectKind::Staircase { levels: 2, hold_ms: 10 };
+        led.run_for_with_end(effect, 15, EndState::Hold).unwrap();
+        assert_eq!(led.pin.get_duty(), 100);
+
+        led.set_end_state(EndState::Hold);
+        assert!(led.heartbeat_with_end(1, 1, 0, EndState::Off).is_err());
+        assert_eq!(led.end_state(), EndState::Hold);
     }
-}
\ No newline at end of file
+}
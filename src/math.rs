@@ -0,0 +1,154 @@
+//! Fixed-point trigonometry for effects running without an FPU
+//!
+//! Angles are `u16` values covering one full turn: `0` is 0°, `16384` is 90°,
+//! `32768` is 180° and `49152` is 270°; the range wraps naturally at 360°.
+//! Results are signed Q1.15 fixed point, i.e. `-32767..=32767` maps to
+//! `-1.0..=1.0`.
+//!
+//! By default the functions use a 257-entry quarter-wave lookup table with
+//! linear interpolation between entries, which keeps the absolute error within
+//! a couple of LSBs without `libm` or floating point. With the `float-math`
+//! feature (for cores with an FPU) they are computed with `f32` and `libm`
+//! instead; the results are the same format either way.
+
+/// One full turn (360°), one past the largest `u16` angle
+pub const FULL_TURN: u32 = 1 << 16;
+
+/// A quarter turn (90°)
+pub const QUARTER_TURN: u16 = 1 << 14;
+
+/// Half a turn (180°)
+pub const HALF_TURN: u16 = 1 << 15;
+
+/// `sin` over the first quadrant in Q1.15, sampled at 256 equal intervals
+#[cfg(not(feature = "float-math"))]
+const QUARTER_SINE: [i16; 257] = [
+    0, 201, 402, 603, 804, 1005, 1206, 1407, 1608, 1809,
+    2009, 2210, 2410, 2611, 2811, 3012, 3212, 3412, 3612, 3811,
+    4011, 4210, 4410, 4609, 4808, 5007, 5205, 5404, 5602, 5800,
+    5998, 6195, 6393, 6590, 6786, 6983, 7179, 7375, 7571, 7767,
+    7962, 8157, 8351, 8545, 8739, 8933, 9126, 9319, 9512, 9704,
+    9896, 10087, 10278, 10469, 10659, 10849, 11039, 11228, 11417, 11605,
+    11793, 11980, 12167, 12353, 12539, 12725, 12910, 13094, 13279, 13462,
+    13645, 13828, 14010, 14191, 14372, 14553, 14732, 14912, 15090, 15269,
+    15446, 15623, 15800, 15976, 16151, 16325, 16499, 16673, 16846, 17018,
+    17189, 17360, 17530, 17700, 17869, 18037, 18204, 18371, 18537, 18703,
+    18868, 19032, 19195, 19357, 19519, 19680, 19841, 20000, 20159, 20317,
+    20475, 20631, 20787, 20942, 21096, 21250, 21403, 21554, 21705, 21856,
+    22005, 22154, 22301, 22448, 22594, 22739, 22884, 23027, 23170, 23311,
+    23452, 23592, 23731, 23870, 24007, 24143, 24279, 24413, 24547, 24680,
+    24811, 24942, 25072, 25201, 25329, 25456, 25582, 25708, 25832, 25955,
+    26077, 26198, 26319, 26438, 26556, 26674, 26790, 26905, 27019, 27133,
+    27245, 27356, 27466, 27575, 27683, 27790, 27896, 28001, 28105, 28208,
+    28310, 28411, 28510, 28609, 28706, 28803, 28898, 28992, 29085, 29177,
+    29268, 29358, 29447, 29534, 29621, 29706, 29791, 29874, 29956, 30037,
+    30117, 30195, 30273, 30349, 30424, 30498, 30571, 30643, 30714, 30783,
+    30852, 30919, 30985, 31050, 31113, 31176, 31237, 31297, 31356, 31414,
+    31470, 31526, 31580, 31633, 31685, 31736, 31785, 31833, 31880, 31926,
+    31971, 32014, 32057, 32098, 32137, 32176, 32213, 32250, 32285, 32318,
+    32351, 32382, 32412, 32441, 32469, 32495, 32521, 32545, 32567, 32589,
+    32609, 32628, 32646, 32663, 32678, 32692, 32705, 32717, 32728, 32737,
+    32745, 32752, 32757, 32761, 32765, 32766, 32767,
+];
+
+/// Sine of `angle` in Q1.15 fixed point
+///
+/// `angle` covers a full turn over `0..=65535` (see the module docs).
+#[cfg(feature = "float-math")]
+pub fn sin_fixed(angle: u16) -> i16 {
+    let radians = f32::from(angle) * (core::f32::consts::TAU / FULL_TURN as f32);
+    libm::roundf(libm::sinf(radians) * 32767.0) as i16
+}
+
+/// Sine of `angle` in Q1.15 fixed point
+///
+/// `angle` covers a full turn over `0..=65535` (see the module docs).
+#[cfg(not(feature = "float-math"))]
+pub fn sin_fixed(angle: u16) -> i16 {
+    let within = angle & (QUARTER_TURN - 1);
+    match angle >> 14 {
+        0 => quarter_wave(within),
+        1 => quarter_wave(QUARTER_TURN - within),
+        2 => -quarter_wave(within),
+        _ => -quarter_wave(QUARTER_TURN - within),
+    }
+}
+
+/// Cosine of `angle` in Q1.15 fixed point
+///
+/// `angle` covers a full turn over `0..=65535` (see the module docs).
+pub fn cos_fixed(angle: u16) -> i16 {
+    sin_fixed(angle.wrapping_add(QUARTER_TURN))
+}
+
+/// Interpolate the quarter-wave table at `x` in `0..=QUARTER_TURN`
+#[cfg(not(feature = "float-math"))]
+fn quarter_wave(x: u16) -> i16 {
+    let index = usize::from(x >> 6);
+    if index >= QUARTER_SINE.len() - 1 {
+        return QUARTER_SINE[QUARTER_SINE.len() - 1];
+    }
+    let frac = i32::from(x & 0x3F);
+    let a = i32::from(QUARTER_SINE[index]);
+    let b = i32::from(QUARTER_SINE[index + 1]);
+    (a + (b - a) * frac / 64) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that `actual` is within `tolerance` of `expected`.
+    fn assert_close(actual: i16, expected: i16, tolerance: i16) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} ± {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    /// Tests sine at the cardinal angles, which must be exact.
+    #[test]
+    fn test_sin_cardinal_angles() {
+        assert_eq!(sin_fixed(0), 0);
+        assert_eq!(sin_fixed(QUARTER_TURN), 32767);
+        assert_eq!(sin_fixed(HALF_TURN), 0);
+        assert_eq!(sin_fixed(HALF_TURN + QUARTER_TURN), -32767);
+    }
+
+    /// Tests cosine at the cardinal angles, which must be exact.
+    #[test]
+    fn test_cos_cardinal_angles() {
+        assert_eq!(cos_fixed(0), 32767);
+        assert_eq!(cos_fixed(QUARTER_TURN), 0);
+        assert_eq!(cos_fixed(HALF_TURN), -32767);
+        assert_eq!(cos_fixed(HALF_TURN + QUARTER_TURN), 0);
+    }
+
+    /// Tests intermediate angles against known values.
+    ///
+    /// sin(30°) = 0.5, sin(45°) = 0.7071, sin(60°) = 0.8660 and their
+    /// negatives in the lower half-turn.
+    #[test]
+    fn test_sin_known_values() {
+        assert_close(sin_fixed(5461), 16384, 2);
+        assert_close(sin_fixed(8192), 23170, 2);
+        assert_close(sin_fixed(10923), 28378, 2);
+        assert_close(sin_fixed(HALF_TURN + 8192), -23170, 2);
+        assert_close(sin_fixed(u16::MAX), -3, 2);
+    }
+
+    /// Tests the identity sin² + cos² = 1 across the whole circle.
+    #[test]
+    fn test_pythagorean_identity() {
+        for angle in (0..FULL_TURN).step_by(97) {
+            let s = i64::from(sin_fixed(angle as u16));
+            let c = i64::from(cos_fixed(angle as u16));
+            let sum = s * s + c * c;
+            let one = 32767i64 * 32767;
+            assert!((sum - one).abs() < one / 1000, "angle {}", angle);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Checked percentages for brightness settings
+//!
+//! A [`Percent`] can only hold `0..=100`, so a duty value cannot be passed
+//! where a percentage is expected (or the other way round) by accident.
+
+#[cfg(feature = "defmt")]
+use defmt::Format;
+
+/// A percentage in `0..=100`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(Format))]
+pub struct Percent(u8);
+
+impl Percent {
+    /// 0%
+    pub const ZERO: Self = Self(0);
+
+    /// 100%
+    pub const FULL: Self = Self(100);
+
+    /// Create a percentage, returning `None` if `value` is above `100`
+    pub const fn new(value: u8) -> Option<Self> {
+        if value > 100 {
+            None
+        } else {
+            Some(Self(value))
+        }
+    }
+
+    /// Create a percentage from a literal
+    ///
+    /// Panics if `value` is above `100`; in a `const` context that is a
+    /// compile error, e.g. `const DIM: Percent = Percent::from_literal(20);`.
+    pub const fn from_literal(value: u8) -> Self {
+        match Self::new(value) {
+            Some(percent) => percent,
+            None => panic!("percentage above 100"),
+        }
+    }
+
+    /// The percentage as a number in `0..=100`
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Scale `value` by this percentage, rounding down
+    pub const fn of(self, value: u32) -> u32 {
+        (value as u64 * self.0 as u64 / 100) as u32
+    }
+}
+
+impl From<Percent> for u8 {
+    fn from(percent: Percent) -> Self {
+        percent.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that only `0..=100` is accepted and scaling rounds down.
+    #[test]
+    fn test_percent() {
+        assert_eq!(Percent::new(100), Some(Percent::FULL));
+        assert_eq!(Percent::new(101), None);
+        assert_eq!(Percent::from_literal(0), Percent::ZERO);
+
+        const HALF: Percent = Percent::from_literal(50);
+        assert_eq!(HALF.get(), 50);
+        assert_eq!(HALF.of(255), 127);
+        assert_eq!(Percent::FULL.of(u32::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,140 @@
+//! Stepping linearly between two duty values
+//!
+//! A [`Ramp`] walks from one duty to another in a fixed number of equal
+//! steps. The built-in effects move between levels with it, and it serves
+//! custom effects the same way:
+//!
+//! ```ignore
+//! for duty in Ramp::new(0, 1000, 50) {
+//!     pwm.set_duty(duty as u16);
+//!     delay.delay_ms(20u32);
+//! }
+//! ```
+//!
+//! The values are computed in 1/256 duty steps internally, so the steps
+//! stay evenly spaced even when there are more steps than duty values;
+//! yielded values drop the fraction.
+
+/// Scale `value` by `numerator / denominator` in 1/256 steps.
+///
+/// Keeps the remainder of the division as an 8-bit fraction instead of
+/// dropping it, without overflowing for any `u32` inputs.
+///
+/// # Arguments
+///
+/// * `value` - The value to scale.
+/// * `numerator` - The numerator of the scale factor.
+/// * `denominator` - The non-zero denominator of the scale factor.
+fn scale_q8(value: u32, numerator: u32, denominator: u32) -> u64 {
+    let product = u64::from(value) * u64::from(numerator);
+    let whole = product / u64::from(denominator);
+    let fraction = (product % u64::from(denominator)) * 256 / u64::from(denominator);
+    (whole << 8) | fraction
+}
+
+/// Iterator over the duties of a linear ramp from `from` to `to`
+///
+/// Yields `steps + 1` values: exactly `from` first, exactly `to` last and
+/// evenly spaced values in between. Falling ramps (`to < from`) work the
+/// same way.
+#[derive(Debug, Clone)]
+pub struct Ramp {
+    from: u32,
+    to: u32,
+    steps: u32,
+    position: u64,
+}
+
+impl Ramp {
+    /// Ramp from `from` to `to` in `steps` equal steps
+    ///
+    /// A `steps` of `0` is taken as `1`, jumping straight from `from` to
+    /// `to`.
+    pub fn new(from: u32, to: u32, steps: u32) -> Self {
+        Self {
+            from,
+            to,
+            steps: steps.max(1),
+            position: 0,
+        }
+    }
+
+    /// Number of steps between `from` and `to`
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    /// Number of values yielded so far, one past the step of the last one
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The value at `step` (`0..=steps`), in 1/256 duty steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - The step to evaluate; `0` is `from` and `steps` is `to`.
+    pub(crate) fn fine_at(&self, step: u32) -> u64 {
+        let offset = scale_q8(self.from.abs_diff(self.to), step, self.steps);
+        let start = u64::from(self.from) << 8;
+        if self.to >= self.from {
+            start + offset
+        } else {
+            start - offset
+        }
+    }
+
+    /// Advance like `next`, returning the value in 1/256 duty steps.
+    pub(crate) fn next_fine(&mut self) -> Option<u64> {
+        if self.position > u64::from(self.steps) {
+            return None;
+        }
+        let value = self.fine_at(self.position as u32);
+        self.position += 1;
+        Some(value)
+    }
+}
+
+impl Iterator for Ramp {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        self.next_fine().map(|value| (value >> 8) as u32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (u64::from(self.steps) + 1 - self.position) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Ramp {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that ramps hit both endpoints with the requested number of steps.
+    #[test]
+    fn test_ramp_endpoints_and_steps() {
+        let rising = Ramp::new(10, 100, 9);
+        assert_eq!(rising.len(), 10);
+        let values: [u32; 10] = core::array::from_fn({
+            let mut ramp = rising.clone();
+            move |_| ramp.next().unwrap()
+        });
+        assert_eq!(values, [10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
+
+        let mut falling = Ramp::new(u32::MAX, 0, 7);
+        assert_eq!(falling.next(), Some(u32::MAX));
+        assert_eq!(falling.by_ref().last(), Some(0));
+        assert_eq!(falling.next(), None);
+
+        // More steps than values, and the zero-step jump.
+        assert_eq!(Ramp::new(0, 3, 1000).count(), 1001);
+        assert_eq!(Ramp::new(0, 3, 1000).last(), Some(3));
+        assert_eq!(Ramp::new(5, 9, 0).steps(), 1);
+        assert!(Ramp::new(5, 9, 0).eq([5, 9]));
+        assert!(Ramp::new(7, 7, 2).eq([7, 7, 7]));
+    }
+}
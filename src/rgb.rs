@@ -0,0 +1,214 @@
+//! Colour effects on an RGB LED
+//!
+//! An [`RgbEffect`] drives the red, green and blue channels of one LED as
+//! three `LEDEffect`s, taking colours as 8-bit perceptual components.
+//! Each channel has its own gamma curve, because the three dies differ in
+//! efficiency and in how the eye perceives them: with a single curve,
+//! whites and pastels come out tinted.
+//!
+//! ```ignore
+//! let mut led = RgbEffect::new(ch_red, ch_green, ch_blue)?;
+//! led.set_gamma_tables(&RED_CURVE, &GAMMA_2_2, &BLUE_CURVE);
+//! led.fade_to(255, 180, 40, 1500)?; // warm white
+//! ```
+
+use embedded_hal::PwmPin;
+
+use crate::gamma::{self, GAMMA_2_2};
+use crate::{DutyValue, Error, LEDEffect, Ramp};
+
+/// Three PWM channels driven as one RGB LED
+///
+/// Every channel spans its pin's full `0..=get_max_duty()` range. Colour
+/// components are perceptual (`0..=255`) and go through the channel's gamma
+/// table before being written. Per-channel settings such as inversion or
+/// a safe maximum are made on the channels themselves (see `red`, `green`
+/// and `blue`); the timing of fades follows the red channel's settings.
+pub struct RgbEffect<'a, R, G, B>
+where
+    R: PwmPin,
+    G: PwmPin,
+    B: PwmPin,
+    R::Duty: DutyValue,
+    G::Duty: DutyValue,
+    B::Duty: DutyValue,
+{
+    red: LEDEffect<'a, R>,
+    green: LEDEffect<'a, G>,
+    blue: LEDEffect<'a, B>,
+    tables: [&'static [u16; 256]; 3],
+    color: [u8; 3],
+}
+
+impl<'a, R, G, B> RgbEffect<'a, R, G, B>
+where
+    R: PwmPin,
+    G: PwmPin,
+    B: PwmPin,
+    R::Duty: DutyValue,
+    G::Duty: DutyValue,
+    B::Duty: DutyValue,
+{
+    /// Drive an RGB LED from its three channels
+    ///
+    /// All channels start with the gamma 2.2 curve. The LED is not written
+    /// until the first colour is set; `color` reports black until then.
+    ///
+    /// Returns `Error::Pwm` if a pin's maximum duty is `0` (a misconfigured
+    /// timer) and `Error::InvalidParameter` if it is below `2`.
+    pub fn new(red: R, green: G, blue: B) -> Result<Self, Error> {
+        Ok(Self {
+            red: full_range(red)?,
+            green: full_range(green)?,
+            blue: full_range(blue)?,
+            tables: [&GAMMA_2_2; 3],
+            color: [0; 3],
+        })
+    }
+
+    /// Use separate gamma tables for the red, green and blue channels
+    ///
+    /// Each table samples its curve like `gamma::GAMMA_2_2`; see
+    /// `gamma::correct_with`. Takes effect with the next colour written.
+    pub fn set_gamma_tables(
+        &mut self,
+        red: &'static [u16; 256],
+        green: &'static [u16; 256],
+        blue: &'static [u16; 256],
+    ) {
+        self.tables = [red, green, blue];
+    }
+
+    /// The colour most recently set, as `[red, green, blue]`
+    pub fn color(&self) -> [u8; 3] {
+        self.color
+    }
+
+    /// Show a colour immediately
+    ///
+    /// Only fails in verify mode (see `LEDEffect::set_verify`).
+    pub fn set_color(&mut self, r: u8, g: u8, b: u8) -> Result<(), Error> {
+        self.fade_to(r, g, b, 0)
+    }
+
+    /// Fade from the current colour to `(r, g, b)` over `duration_ms`
+    ///
+    /// All three channels move together in linear steps of their duties,
+    /// at most one step per millisecond and per duty value of the channel
+    /// that moves furthest (or one per frame, see `LEDEffect::set_fps`).
+    /// The colour is held once reached. Only fails in verify mode.
+    pub fn fade_to(&mut self, r: u8, g: u8, b: u8, duration_ms: u32) -> Result<(), Error> {
+        let from = [
+            LEDEffect::<R>::to_u32(self.red.current_duty()),
+            LEDEffect::<G>::to_u32(self.green.current_duty()),
+            LEDEffect::<B>::to_u32(self.blue.current_duty()),
+        ];
+        let to = [
+            target(&self.red, self.tables[0], r),
+            target(&self.green, self.tables[1], g),
+            target(&self.blue, self.tables[2], b),
+        ];
+        self.color = [r, g, b];
+
+        let distance = (0..3).map(|i| from[i].abs_diff(to[i])).max().unwrap_or(0);
+        let steps = self
+            .red
+            .frames_in(duration_ms)
+            .unwrap_or(distance.min(duration_ms))
+            .max(1);
+        let mut ramps = [0, 1, 2].map(|i| Ramp::new(from[i], to[i], steps));
+        for ramp in ramps.iter_mut() {
+            ramp.next_fine(); // the current colour is already showing
+        }
+
+        let mut elapsed = 0;
+        for step in 1..=u64::from(steps) {
+            let [red, green, blue] = ramps.each_mut().map(|ramp| ramp.next_fine().unwrap_or(0));
+            self.red.write_fine(red);
+            self.green.write_fine(green);
+            self.blue.write_fine(blue);
+
+            let next = (u64::from(duration_ms) * step / u64::from(steps)) as u32;
+            self.red.delay_ms(next - elapsed);
+            elapsed = next;
+            if self.red.stopped() || self.green.stopped() || self.blue.stopped() {
+                break;
+            }
+        }
+        self.red.flush();
+        self.green.flush();
+        self.blue.flush();
+        let red = self.red.complete();
+        let green = self.green.complete();
+        let blue = self.blue.complete();
+        red.and(green).and(blue)
+    }
+
+    /// Flash `(r, g, b)` for `flash_ms`, then fade back to the previous colour
+    ///
+    /// Meant for momentary feedback on top of a base colour, e.g. a green
+    /// flash on success. The colour showing before the call is remembered,
+    /// the flash colour appears at once and is held for `flash_ms`, and the
+    /// LED then fades back to the remembered colour over the same time.
+    /// Only fails in verify mode; a failed write still attempts the return.
+    pub fn flash(&mut self, r: u8, g: u8, b: u8, flash_ms: u32) -> Result<(), Error> {
+        let [base_r, base_g, base_b] = self.color;
+        let flashed = self.set_color(r, g, b);
+        self.red.delay_ms(flash_ms);
+        let restored = self.fade_to(base_r, base_g, base_b, flash_ms);
+        flashed.and(restored)
+    }
+
+    /// The red channel, for its settings and single-channel effects
+    pub fn red(&mut self) -> &mut LEDEffect<'a, R> {
+        &mut self.red
+    }
+
+    /// The green channel, for its settings and single-channel effects
+    pub fn green(&mut self) -> &mut LEDEffect<'a, G> {
+        &mut self.green
+    }
+
+    /// The blue channel, for its settings and single-channel effects
+    pub fn blue(&mut self) -> &mut LEDEffect<'a, B> {
+        &mut self.blue
+    }
+
+    /// Destroy the effect and return the red, green and blue pins
+    pub fn destroy(self) -> (R, G, B) {
+        (self.red.destroy(), self.green.destroy(), self.blue.destroy())
+    }
+}
+
+/// Wrap `pin` in an `LEDEffect` spanning its full duty range.
+///
+/// # Arguments
+///
+/// * `pin` - The PWM channel of one colour.
+fn full_range<'a, P>(pin: P) -> Result<LEDEffect<'a, P>, Error>
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    let max = pin.get_max_duty();
+    if LEDEffect::<P>::to_u32(max) == 0 {
+        return Err(Error::Pwm);
+    }
+    LEDEffect::new(pin, LEDEffect::<P>::from_u32(0), max)
+}
+
+/// Duty of `channel` for the perceptual colour `component`.
+///
+/// # Arguments
+///
+/// * `channel` - The channel to compute the duty for.
+/// * `table` - The channel's gamma table.
+/// * `component` - The colour component, `0..=255`.
+fn target<P>(channel: &LEDEffect<'_, P>, table: &[u16; 256], component: u8) -> u32
+where
+    P: PwmPin,
+    P::Duty: DutyValue,
+{
+    let linear = gamma::correct_with(table, u16::from(component) * 257);
+    LEDEffect::<P>::to_u32(channel.duty_from_fraction(linear.into(), u16::MAX.into()))
+}
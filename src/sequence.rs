@@ -0,0 +1,159 @@
+//! Declarative sequencing of LED effects
+//!
+//! An [`EffectSequence`] stores a fixed-capacity list of [`EffectStep`]s and
+//! runs them one after another on a [`LEDEffect`]. The capacity `N` is a const
+//! generic, so sequences live on the stack (or in a `static`) without any
+//! allocation.
+
+use embedded_hal::PwmPin;
+use heapless::Vec;
+
+use crate::{Delay, DutyValue, Error, LEDEffect};
+
+/// A single step of an [`EffectSequence`]
+///
+/// Each variant mirrors one of the `LEDEffect` methods together with the
+/// parameters it is called with. `D` is the duty type of the PWM pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectStep<D> {
+    /// Fade to `target` over `duration_ms` and hold it (see `LEDEffect::fade`)
+    Fade {
+        /// Duty cycle to fade to
+        target: D,
+        /// Duration of the fade in milliseconds
+        duration_ms: u32,
+    },
+    /// Keep the current duty cycle for `duration_ms`
+    Hold {
+        /// Duration of the hold in milliseconds
+        duration_ms: u32,
+    },
+    /// Run a breathing cycle (see `LEDEffect::breath`)
+    Breath {
+        /// Nominal duration of the breathing cycle in milliseconds
+        duration: u32,
+    },
+    /// Run a heartbeat (see `LEDEffect::heartbeat`)
+    Heartbeat {
+        /// Number of beats to flash
+        flash_beats: u32,
+        /// Number of beats per group
+        grouped_as: u32,
+        /// Beats per minute
+        bpm: u32,
+    },
+}
+
+/// A fixed-capacity list of effect steps executed in order
+///
+/// ```ignore
+/// let mut seq: EffectSequence<u32, 4> = EffectSequence::new();
+/// seq.push(EffectStep::Fade { target: 255, duration_ms: 500 })?;
+/// seq.push(EffectStep::Hold { duration_ms: 1000 })?;
+/// seq.push(EffectStep::Heartbeat { flash_beats: 3, grouped_as: 3, bpm: 60 })?;
+/// seq.push(EffectStep::Fade { target: 5, duration_ms: 500 })?;
+/// seq.run(&mut led)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct EffectSequence<D, const N: usize> {
+    steps: Vec<EffectStep<D>, N>,
+}
+
+impl<D, const N: usize> EffectSequence<D, N>
+where
+    D: Copy,
+{
+    /// Create an empty sequence
+    pub const fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    /// Append a step to the end of the sequence
+    ///
+    /// Returns the step back as `Err` if the sequence is already full.
+    pub fn push(&mut self, step: EffectStep<D>) -> Result<(), EffectStep<D>> {
+        self.steps.push(step)
+    }
+
+    /// Remove all steps from the sequence
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Steps currently stored in the sequence
+    pub fn steps(&self) -> &[EffectStep<D>] {
+        &self.steps
+    }
+
+    /// Iterate over the stored steps in execution order
+    pub fn iter(&self) -> core::slice::Iter<'_, EffectStep<D>> {
+        self.steps.iter()
+    }
+
+    /// Number of steps in the sequence
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns `true` if the sequence holds no steps
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Run every step in order on `led`
+    ///
+    /// Execution stops at the first step that returns an error, which is
+    /// then propagated to the caller.
+    pub fn run<PWM, DELAY>(&self, led: &mut LEDEffect<'_, PWM, DELAY>) -> Result<(), Error>
+    where
+        PWM: PwmPin<Duty = D>,
+        D: DutyValue,
+        DELAY: Delay,
+    {
+        for step in self.steps.iter() {
+            match *step {
+                EffectStep::Fade { target, duration_ms } => led.fade(target, duration_ms)?,
+                EffectStep::Hold { duration_ms } => led.delay_ms(duration_ms),
+                EffectStep::Breath { duration } => led.breath(duration)?,
+                EffectStep::Heartbeat {
+                    flash_beats,
+                    grouped_as,
+                    bpm,
+                } => led.heartbeat(flash_beats, grouped_as, bpm)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<D, const N: usize> Default for EffectSequence<D, N>
+where
+    D: Copy,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D, const N: usize> FromIterator<EffectStep<D>> for EffectSequence<D, N>
+where
+    D: Copy,
+{
+    /// Collect steps into a sequence, silently dropping any beyond capacity `N`
+    fn from_iter<I: IntoIterator<Item = EffectStep<D>>>(iter: I) -> Self {
+        let mut seq = Self::new();
+        for step in iter.into_iter().take(N) {
+            let _ = seq.push(step);
+        }
+        seq
+    }
+}
+
+impl<'a, D, const N: usize> IntoIterator for &'a EffectSequence<D, N> {
+    type Item = &'a EffectStep<D>;
+    type IntoIter = core::slice::Iter<'a, EffectStep<D>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.steps.iter()
+    }
+}
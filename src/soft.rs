@@ -0,0 +1,235 @@
+//! Software PWM for pins without a PWM peripheral
+//!
+//! A [`SoftPwm`] wraps a plain `OutputPin` (a GPIO, an I/O expander pin)
+//! and presents it as a `PwmPin`, so the effects can dim it unchanged.
+//! There is no timer behind it: the pin is toggled by the effect's own
+//! busy-wait. Create the effect with `LEDEffect::new_soft` so that every
+//! delay is spent bit-banging the configured duty instead of idling:
+//!
+//! ```ignore
+//! let pin = SoftPwm::new(gpio_pa5, 200, 100)?; // 200 Hz, 101 levels
+//! let mut led = LEDEffect::new_soft(pin, 0, 100)?;
+//! led.breath(3000)?;
+//! ```
+//!
+//! The PWM only runs while an effect is waiting. In between, the pin holds
+//! its last level; a duty of `0` or of `get_max_duty()` always leaves it
+//! steadily low or high. Interrupts that stretch the busy-wait show up as
+//! flicker, and the wait is never handed to a `set_yield` hook.
+
+#[cfg(target_arch = "arm")]
+use cortex_m::asm;
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::PwmPin;
+
+use crate::Error;
+
+/// An `OutputPin` driven as a `PwmPin` in software
+///
+/// The duty is a `u16` in `0..=max_duty`, set at construction together
+/// with the PWM frequency. Errors reported by the pin are ignored, as
+/// `PwmPin` has no way to return them.
+pub struct SoftPwm<P>
+where
+    P: OutputPin,
+{
+    pin: P,
+    frequency_hz: u32,
+    max_duty: u16,
+    duty: u16,
+    enabled: bool,
+}
+
+impl<P> SoftPwm<P>
+where
+    P: OutputPin,
+{
+    /// Drive `pin` at `frequency_hz` with `max_duty + 1` brightness levels
+    ///
+    /// The pin starts disabled and low; `LEDEffect` enables it with its
+    /// first write. Higher frequencies flicker less but leave fewer clock
+    /// cycles per level, so keep `frequency_hz * max_duty` well below the
+    /// core clock.
+    ///
+    /// Returns `Error::InvalidParameter` if `frequency_hz` or `max_duty` is
+    /// zero.
+    pub fn new(mut pin: P, frequency_hz: u32, max_duty: u16) -> Result<Self, Error> {
+        if frequency_hz == 0 || max_duty == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let _ = pin.set_low();
+        Ok(Self {
+            pin,
+            frequency_hz,
+            max_duty,
+            duty: 0,
+            enabled: false,
+        })
+    }
+
+    /// PWM frequency in Hz
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz
+    }
+
+    /// Release the wrapped pin
+    pub fn release(self) -> P {
+        self.pin
+    }
+
+    /// Bit-bang the current duty for `ms` milliseconds.
+    ///
+    /// Runs whole PWM periods, each high for its share of the period and
+    /// low for the rest; a final partial period is cut short so that the
+    /// total time is exact.
+    ///
+    /// # Arguments
+    ///
+    /// * `ms` - The time to spend, in milliseconds.
+    /// * `cycles_per_ms` - Core clock cycles in one millisecond.
+    pub(crate) fn drive(&mut self, ms: u32, cycles_per_ms: u32) {
+        let period = (u64::from(cycles_per_ms) * 1_000 / u64::from(self.frequency_hz)).max(1);
+        let high = if self.enabled {
+            period * u64::from(self.duty) / u64::from(self.max_duty)
+        } else {
+            0
+        };
+
+        let mut left = u64::from(ms) * u64::from(cycles_per_ms);
+        while left > 0 {
+            let slice = left.min(period);
+            let on = high.min(slice);
+            if on > 0 {
+                let _ = self.pin.set_high();
+                spin(on);
+            }
+            if slice > on {
+                let _ = self.pin.set_low();
+                spin(slice - on);
+            }
+            left -= slice;
+        }
+    }
+
+    /// Put the pin in the steady state of the current duty, if it has one.
+    fn settle(&mut self) {
+        if !self.enabled || self.duty == 0 {
+            let _ = self.pin.set_low();
+        } else if self.duty == self.max_duty {
+            let _ = self.pin.set_high();
+        }
+    }
+}
+
+impl<P> PwmPin for SoftPwm<P>
+where
+    P: OutputPin,
+{
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        self.enabled = false;
+        self.settle();
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true;
+        self.settle();
+    }
+
+    fn get_duty(&self) -> u16 {
+        self.duty
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.duty = duty.min(self.max_duty);
+        self.settle();
+    }
+}
+
+/// Busy-wait for `cycles` core clock cycles.
+///
+/// # Arguments
+///
+/// * `cycles` - The number of cycles to wait.
+#[inline(always)]
+fn spin(cycles: u64) {
+    // `asm::delay` only exists on Cortex-M; host builds (tests) skip the wait.
+    #[cfg(target_arch = "arm")]
+    asm::delay(cycles.min(u64::from(u32::MAX)) as u32);
+    #[cfg(not(target_arch = "arm"))]
+    let _ = cycles;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    /// Output pin that counts its transitions.
+    #[derive(Default)]
+    struct MockOutput {
+        high: bool,
+        rises: u32,
+        falls: u32,
+    }
+
+    impl OutputPin for MockOutput {
+        type Error = Infallible;
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.rises += u32::from(!self.high);
+            self.high = true;
+            Ok(())
+        }
+
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.falls += u32::from(self.high);
+            self.high = false;
+            Ok(())
+        }
+    }
+
+    /// Tests that driving toggles once per period and settles at the extremes.
+    #[test]
+    fn test_soft_pwm_periods() {
+        assert!(SoftPwm::new(MockOutput::default(), 0, 100).is_err());
+        assert!(SoftPwm::new(MockOutput::default(), 100, 0).is_err());
+
+        let mut soft = SoftPwm::new(MockOutput::default(), 1_000, 100).unwrap();
+        soft.set_duty(50);
+        soft.drive(5, 1_000);
+        assert_eq!(soft.pin.rises, 0, "disabled pins stay low");
+
+        soft.enable();
+        soft.drive(5, 1_000);
+        assert_eq!((soft.pin.rises, soft.pin.falls), (5, 5));
+
+        soft.set_duty(500);
+        assert_eq!(soft.get_duty(), 100);
+        assert!(soft.pin.high);
+        soft.drive(5, 1_000);
+        assert_eq!(soft.pin.falls, 5, "full duty never goes low");
+
+        soft.set_duty(0);
+        assert!(!soft.pin.high);
+        soft.drive(5, 1_000);
+        assert_eq!(soft.pin.rises, 6, "zero duty never goes high");
+    }
+
+    /// Tests that an effect's delays bit-bang the pin.
+    #[test]
+    fn test_effect_on_soft_pwm() {
+        let soft = SoftPwm::new(MockOutput::default(), 1_000, 100).unwrap();
+        let mut led = crate::LEDEffect::new_soft(soft, 0, 100).unwrap();
+        led.fade(50, 0).unwrap();
+        led.fade(50, 20).unwrap();
+        let soft = led.destroy();
+        assert_eq!(soft.get_duty(), 50);
+        assert_eq!((soft.pin.rises, soft.pin.falls), (20, 20));
+    }
+}
@@ -0,0 +1,173 @@
+//! Composable brightness sources
+//!
+//! A [`BrightnessSource`] maps time since the start of an effect to a
+//! brightness level in `0..=65535`, much like an oscillator in a modular
+//! synthesizer. Sources combine with [`BrightnessSource::mul`] and
+//! [`BrightnessSource::add`] into new sources, and any source is played on a
+//! pin with `LEDEffect::play_source`:
+//!
+//! ```ignore
+//! // A 3 s breath whose depth swells and fades over 20 s
+//! let mut source = Sine::new(3_000).mul(Triangle::new(20_000));
+//! led.play_source(&mut source, 60_000, 20)?;
+//! ```
+//!
+//! Levels are mapped linearly onto `[pwm_min, pwm_max]`, like the samples
+//! of `LEDEffect::play_static`.
+
+use crate::math;
+
+/// A brightness level that varies over time
+pub trait BrightnessSource {
+    /// The level at `t_ms` milliseconds into the effect, in `0..=65535`
+    fn level(&mut self, t_ms: u32) -> u16;
+
+    /// Multiply this source by `other`, so that `other` scales its amplitude
+    ///
+    /// `65535` is `1.0`: a product with `Constant(u16::MAX)` is unchanged.
+    fn mul<B>(self, other: B) -> Product<Self, B>
+    where
+        Self: Sized,
+        B: BrightnessSource,
+    {
+        Product(self, other)
+    }
+
+    /// Add `other` to this source, saturating at `65535`
+    fn add<B>(self, other: B) -> Sum<Self, B>
+    where
+        Self: Sized,
+        B: BrightnessSource,
+    {
+        Sum(self, other)
+    }
+}
+
+impl<S: BrightnessSource + ?Sized> BrightnessSource for &mut S {
+    fn level(&mut self, t_ms: u32) -> u16 {
+        (**self).level(t_ms)
+    }
+}
+
+/// A fixed level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Constant(pub u16);
+
+impl BrightnessSource for Constant {
+    fn level(&mut self, _t_ms: u32) -> u16 {
+        self.0
+    }
+}
+
+/// A sine wave between `0` and `65535`, starting at `0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sine {
+    period_ms: u32,
+}
+
+impl Sine {
+    /// A sine wave repeating every `period_ms`; a period of `0` stays at `0`
+    pub const fn new(period_ms: u32) -> Self {
+        Self { period_ms }
+    }
+}
+
+impl BrightnessSource for Sine {
+    fn level(&mut self, t_ms: u32) -> u16 {
+        let Some(within) = t_ms.checked_rem(self.period_ms) else {
+            return 0;
+        };
+        let angle = (u64::from(within) << 16) / u64::from(self.period_ms);
+        // (1 - cos) / 2, so the wave starts at its trough
+        let cos = i32::from(math::cos_fixed(angle as u16));
+        ((32_767 - cos) as u32 * u32::from(u16::MAX) / 65_534) as u16
+    }
+}
+
+/// A triangle wave between `0` and `65535`, starting at `0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Triangle {
+    period_ms: u32,
+}
+
+impl Triangle {
+    /// A triangle wave repeating every `period_ms`; a period of `0` stays at `0`
+    pub const fn new(period_ms: u32) -> Self {
+        Self { period_ms }
+    }
+}
+
+impl BrightnessSource for Triangle {
+    fn level(&mut self, t_ms: u32) -> u16 {
+        let Some(within) = t_ms.checked_rem(self.period_ms) else {
+            return 0;
+        };
+        let period = u64::from(self.period_ms);
+        let distance = (2 * u64::from(within)).abs_diff(period);
+        (u64::from(u16::MAX) * (period - distance) / period) as u16
+    }
+}
+
+/// Two sources multiplied, created with [`BrightnessSource::mul`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Product<A, B>(A, B);
+
+impl<A: BrightnessSource, B: BrightnessSource> BrightnessSource for Product<A, B> {
+    fn level(&mut self, t_ms: u32) -> u16 {
+        let product = u32::from(self.0.level(t_ms)) * u32::from(self.1.level(t_ms));
+        (product / u32::from(u16::MAX)) as u16
+    }
+}
+
+/// Two sources added, created with [`BrightnessSource::add`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sum<A, B>(A, B);
+
+impl<A: BrightnessSource, B: BrightnessSource> BrightnessSource for Sum<A, B> {
+    fn level(&mut self, t_ms: u32) -> u16 {
+        self.0.level(t_ms).saturating_add(self.1.level(t_ms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests the built-in waves at their key points.
+    #[test]
+    fn test_waves() {
+        let mut sine = Sine::new(1_000);
+        assert_eq!(sine.level(0), 0);
+        assert_eq!(sine.level(500), u16::MAX);
+        assert!(sine.level(250).abs_diff(32_768) < 16);
+        assert_eq!(sine.level(1_000), 0);
+
+        let mut triangle = Triangle::new(1_000);
+        assert_eq!(triangle.level(0), 0);
+        assert_eq!(triangle.level(250), 32_767);
+        assert_eq!(triangle.level(500), u16::MAX);
+        assert_eq!(triangle.level(1_250), 32_767);
+
+        assert_eq!(Sine::new(0).level(123), 0);
+        assert_eq!(Triangle::new(0).level(123), 0);
+    }
+
+    /// Tests that combined sources multiply and add their levels.
+    #[test]
+    fn test_combinators() {
+        let mut half = Triangle::new(1_000).mul(Constant(32_768));
+        assert_eq!(half.level(500), 32_768);
+        assert_eq!(half.level(0), 0);
+
+        let mut full = Constant(u16::MAX).mul(Constant(u16::MAX));
+        assert_eq!(full.level(0), u16::MAX);
+
+        let mut sum = Constant(40_000).add(Triangle::new(1_000));
+        assert_eq!(sum.level(0), 40_000);
+        assert_eq!(sum.level(500), u16::MAX);
+
+        let mut inner = Constant(100);
+        let mut nested = (&mut inner).add(Constant(1));
+        assert_eq!(nested.level(0), 101);
+    }
+}
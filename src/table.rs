@@ -0,0 +1,57 @@
+//! Pre-rendered effect timelines
+//!
+//! A table is a `&'static [u16]` of brightness samples played back with
+//! `LEDEffect::play_static`. Each sample spans `0..=65535` and is mapped
+//! linearly onto `[pwm_min, pwm_max]`, so tables are independent of the PWM
+//! resolution. Generating them with the `const fn`s below bakes the curve
+//! (including gamma) into flash and leaves no per-frame math at runtime:
+//!
+//! ```ignore
+//! static BREATH: [u16; 128] = led_effects::table::breath_table();
+//! led.play_static(&BREATH, 20)?;
+//! ```
+
+use crate::gamma;
+
+/// Render one gamma-corrected breathing cycle into `N` samples
+///
+/// The perceptual level rises linearly over the first half of the table and
+/// falls over the second, and every sample is passed through the gamma 2.2
+/// curve, so playback brightens and dims evenly to the eye. The first and
+/// last samples are `0`; the peak reaches `65535` when `N` is odd.
+pub const fn breath_table<const N: usize>() -> [u16; N] {
+    let mut table = [0u16; N];
+    if N < 2 {
+        return table;
+    }
+
+    let last = (N - 1) as u64;
+    let mut i = 0;
+    while i < N {
+        let distance = (2 * i as u64).abs_diff(last);
+        let level = (u16::MAX as u64 * (last - distance) / last) as u16;
+        table[i] = gamma::correct_lut(level);
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests that the breathing table is symmetric and hits both extremes.
+    #[test]
+    fn test_breath_table() {
+        const TABLE: [u16; 33] = breath_table();
+        assert_eq!(TABLE[0], 0);
+        assert_eq!(TABLE[16], u16::MAX);
+        assert_eq!(TABLE[32], 0);
+        for i in 0..16 {
+            assert_eq!(TABLE[i], TABLE[32 - i]);
+            assert!(TABLE[i] <= TABLE[i + 1]);
+        }
+        // Gamma keeps the quarter point well below a quarter of full scale.
+        assert!(TABLE[8] < u16::MAX / 4);
+    }
+}